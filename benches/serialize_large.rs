@@ -578,7 +578,16 @@ fn serde_json() -> String {
 
 #[divan::bench]
 fn typed_json() -> String {
-    let data = typed_json::json! {{
+    typed_json_data().to_string()
+}
+
+#[divan::bench]
+fn typed_json_to_string() -> String {
+    serde_json::to_string(&typed_json_data()).unwrap()
+}
+
+fn typed_json_data() -> impl serde::Serialize + std::fmt::Display {
+    typed_json::json! {{
       "description": "Service is a named abstraction of software service (for example, mysql) consisting of local port (for example 3306) that the proxy listens on, and the selector that determines which pods will answer requests sent through the proxy.",
       "properties": {
         "apiVersion": {
@@ -1145,6 +1154,5 @@ fn typed_json() -> String {
       ],
       "$schema": "http://json-schema.org/schema#",
       "type": "object"
-    }};
-    data.to_string()
+    }}
 }
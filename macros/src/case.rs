@@ -0,0 +1,59 @@
+//! `#![rename_all = "..."]`, the same conversions as `typed_json::__private::Case`.
+
+#[derive(Clone, Copy)]
+pub enum Case {
+    None,
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl Case {
+    /// The case named by serde's `rename_all`.
+    pub fn from_name(name: &str) -> Option<Case> {
+        match name {
+            "lowercase" => Some(Case::Lower),
+            "UPPERCASE" => Some(Case::Upper),
+            "PascalCase" => Some(Case::Pascal),
+            "camelCase" => Some(Case::Camel),
+            "snake_case" => Some(Case::Snake),
+            "SCREAMING_SNAKE_CASE" => Some(Case::ScreamingSnake),
+            "kebab-case" => Some(Case::Kebab),
+            "SCREAMING-KEBAB-CASE" => Some(Case::ScreamingKebab),
+            _ => None,
+        }
+    }
+
+    /// Convert the `snake_case` identifier `ident` to this case.
+    pub fn convert(self, ident: &str) -> String {
+        let ident = ident.strip_prefix("r#").unwrap_or(ident);
+        let mut out = String::with_capacity(ident.len());
+        let mut capitalize = matches!(self, Case::Pascal);
+        for c in ident.chars() {
+            match self {
+                Case::None | Case::Snake => out.push(c),
+                Case::Lower => out.push(c.to_ascii_lowercase()),
+                Case::Upper | Case::ScreamingSnake => out.push(c.to_ascii_uppercase()),
+                Case::Kebab if c == '_' => out.push('-'),
+                Case::Kebab => out.push(c),
+                Case::ScreamingKebab if c == '_' => out.push('-'),
+                Case::ScreamingKebab => out.push(c.to_ascii_uppercase()),
+                Case::Pascal | Case::Camel if c == '_' => {
+                    // only capitalize words that follow another word
+                    capitalize = matches!(self, Case::Pascal) || !out.is_empty();
+                }
+                Case::Pascal | Case::Camel if capitalize => {
+                    capitalize = false;
+                    out.push(c.to_ascii_uppercase());
+                }
+                Case::Pascal | Case::Camel => out.push(c),
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,351 @@
+//! Parse JSON text, with `{{expr}}` placeholders, into a [`Value`].
+
+use std::collections::HashSet;
+
+use proc_macro2::{Group, Literal, Span, TokenStream, TokenTree};
+use quote::quote;
+
+use crate::value::{Element, Entry, Value};
+
+/// An error in the JSON text, at a byte offset into it.
+pub struct Error {
+    pub msg: String,
+    pub offset: usize,
+}
+
+impl Error {
+    /// Describe the error along with the line and column it happened at.
+    pub fn describe(&self, src: &str) -> String {
+        let before = &src[..self.offset.min(src.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+        format!("{} at line {} column {}", self.msg, line, column)
+    }
+}
+
+/// Parse a single JSON document.
+///
+/// Placeholders are given the `span` of the macro input they came from, so they can refer to
+/// variables at the call site.
+pub fn parse(src: &str, span: Span) -> Result<Value, Error> {
+    let mut parser = Parser { src, pos: 0, span };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos < src.len() {
+        return Err(parser.error("trailing characters"));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    span: Span,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, msg: impl Into<String>) -> Error {
+        Error {
+            msg: msg.into(),
+            offset: self.pos,
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8, what: &str) -> Result<(), Error> {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(format!("expected {}", what)))
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, Error> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') if self.rest().starts_with("{{") => self.placeholder().map(Value::Expr),
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => {
+                let s = self.string()?;
+                let s = Literal::string(&s);
+                Ok(Value::Expr(quote!(#s)))
+            }
+            Some(b'-' | b'0'..=b'9') => self.number().map(Value::Expr),
+            Some(b'n') => self.keyword("null").map(|_| Value::Null),
+            Some(b't') => self.keyword("true").map(|_| Value::Expr(quote!(true))),
+            Some(b'f') => self.keyword("false").map(|_| Value::Expr(quote!(false))),
+            Some(_) => Err(self.error("expected value")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<(), Error> {
+        if self.rest().starts_with(keyword) {
+            self.pos += keyword.len();
+            Ok(())
+        } else {
+            Err(self.error("expected value"))
+        }
+    }
+
+    /// `{{expr}}`, where `expr` is Rust code. Ends at the first `}}` outside of any braces in
+    /// the expression.
+    fn placeholder(&mut self) -> Result<TokenStream, Error> {
+        self.pos += 2;
+        let start = self.pos;
+        let mut depth = 0usize;
+        loop {
+            let rest = self.rest();
+            if depth == 0 && rest.starts_with("}}") {
+                break;
+            }
+            match rest.chars().next() {
+                Some('{') => depth += 1,
+                Some('}') => depth = depth.saturating_sub(1),
+                Some(_) => {}
+                None => {
+                    return Err(Error {
+                        msg: "unclosed placeholder".to_owned(),
+                        offset: start - 2,
+                    })
+                }
+            }
+            self.pos += rest.chars().next().map_or(1, char::len_utf8);
+        }
+        let expr = &self.src[start..self.pos];
+        self.pos += 2;
+        match expr.trim().parse::<TokenStream>() {
+            Ok(expr) if !expr.is_empty() => Ok(respan(expr, self.span)),
+            _ => Err(Error {
+                msg: format!("invalid placeholder `{}`", expr.trim()),
+                offset: start,
+            }),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, Error> {
+        self.pos += 1;
+        let mut entries = Vec::new();
+        let mut keys = HashSet::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Value::Object(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            let start = self.pos;
+            let key = match self.peek() {
+                Some(b'{') if self.rest().starts_with("{{") => self.placeholder()?,
+                Some(b'"') => {
+                    let key = self.string()?;
+                    if !keys.insert(key.clone()) {
+                        return Err(Error {
+                            msg: format!("duplicate key `{}`", key),
+                            offset: start,
+                        });
+                    }
+                    let key = Literal::string(&key);
+                    quote!(#key)
+                }
+                _ => return Err(self.error("expected string key")),
+            };
+            self.expect(b':', "`:`")?;
+            let value = self.value()?;
+            entries.push(Entry::Pair(Value::Expr(key), value));
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Value::Object(entries));
+                }
+                _ => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, Error> {
+        self.pos += 1;
+        let mut elements = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Value::Array(elements));
+        }
+        loop {
+            elements.push(Element::Value(self.value()?));
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Value::Array(elements));
+                }
+                _ => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let c = match self.rest().chars().next() {
+                Some(c) => c,
+                None => return Err(self.error("unterminated string")),
+            };
+            match c {
+                '"' => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                '\\' => {
+                    self.pos += 1;
+                    let escaped = match self.peek() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            self.pos += 1;
+                            out.push(self.unicode_escape()?);
+                            continue;
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    self.pos += 1;
+                    out.push(escaped);
+                }
+                '\u{0}'..='\u{1f}' => return Err(self.error("control character in string")),
+                c => {
+                    self.pos += c.len_utf8();
+                    out.push(c);
+                }
+            }
+        }
+    }
+
+    /// The `XXXX` of a `\uXXXX` escape, along with the low surrogate that follows a high one.
+    fn unicode_escape(&mut self) -> Result<char, Error> {
+        let high = self.hex4()?;
+        let code = if (0xd800..0xdc00).contains(&high) {
+            if !self.rest().starts_with("\\u") {
+                return Err(self.error("unpaired surrogate"));
+            }
+            self.pos += 2;
+            let low = self.hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(self.error("unpaired surrogate"));
+            }
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| self.error("unpaired surrogate"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, Error> {
+        let hex = self
+            .rest()
+            .get(..4)
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()));
+        match hex {
+            Some(hex) => {
+                self.pos += 4;
+                Ok(u32::from_str_radix(hex, 16).unwrap())
+            }
+            None => Err(self.error("invalid unicode escape")),
+        }
+    }
+
+    /// A number, as the Rust literal that `json!` would have for it. Integers that do not fit
+    /// in an `i32` get a wider type instead.
+    fn number(&mut self) -> Result<TokenStream, Error> {
+        let start = self.pos;
+        let digits = |p: &mut Self| {
+            let from = p.pos;
+            while let Some(b'0'..=b'9') = p.peek() {
+                p.pos += 1;
+            }
+            p.pos > from
+        };
+
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        if self.peek() == Some(b'0') {
+            self.pos += 1;
+        } else if !digits(self) {
+            return Err(self.error("invalid number"));
+        }
+        let mut integer = true;
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            integer = false;
+            if !digits(self) {
+                return Err(self.error("invalid number"));
+            }
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            self.pos += 1;
+            integer = false;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if !digits(self) {
+                return Err(self.error("invalid number"));
+            }
+        }
+
+        let text = &self.src[start..self.pos];
+        let literal = if !integer || text.parse::<i32>().is_ok() {
+            text.to_owned()
+        } else if text.parse::<i64>().is_ok() {
+            format!("{}i64", text)
+        } else if text.parse::<u64>().is_ok() {
+            format!("{}u64", text)
+        } else {
+            format!("{}f64", text)
+        };
+        Ok(literal.parse().unwrap())
+    }
+}
+
+fn respan(tokens: TokenStream, span: Span) -> TokenStream {
+    tokens
+        .into_iter()
+        .map(|mut tt| {
+            if let TokenTree::Group(g) = &tt {
+                let mut group = Group::new(g.delimiter(), respan(g.stream(), span));
+                group.set_span(span);
+                tt = TokenTree::Group(group);
+            } else {
+                tt.set_span(span);
+            }
+            tt
+        })
+        .collect()
+}
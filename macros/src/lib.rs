@@ -0,0 +1,197 @@
+//! Procedural macros for [`typed-json`](https://docs.rs/typed-json).
+//!
+//! These are re-exported by `typed-json` with the `macros` feature, use them from there.
+
+use std::path::PathBuf;
+
+use proc_macro2::{Delimiter, Literal, Span, TokenStream, TokenTree};
+use quote::{quote, quote_spanned};
+
+mod case;
+mod json;
+mod parse;
+mod render;
+mod types;
+mod value;
+
+/// `json!($crate, ...)`
+#[doc(hidden)]
+#[proc_macro]
+pub fn json(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let (krate, input) = split_krate(input.into());
+    match parse::parse(input) {
+        Ok(value) => value.expand(&krate).into(),
+        Err(err) => compile_error(&err.msg, err.span).into(),
+    }
+}
+
+/// `json_const!($crate, ...)`
+#[doc(hidden)]
+#[proc_macro]
+pub fn json_const(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let (krate, input) = split_krate(input.into());
+    let value = match parse::parse(input) {
+        Ok(value) => value,
+        Err(err) => return compile_error(&err.msg, err.span).into(),
+    };
+    let render = render::Render { floats: true };
+    match (render.compact(&value), render.pretty(&value)) {
+        (Ok(compact), Ok(pretty)) => {
+            quote!(#krate::ConstJson::new_unchecked(#compact, #pretty)).into()
+        }
+        (Err((span, msg)), _) | (_, Err((span, msg))) => compile_error(msg, span).into(),
+    }
+}
+
+/// `json_type!($crate, ...)`
+#[doc(hidden)]
+#[proc_macro]
+pub fn json_type(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let (krate, input) = split_krate(input.into());
+    match types::parse(input) {
+        Ok(value) => value.ty(&krate).into(),
+        Err(err) => compile_error(&err.msg, err.span).into(),
+    }
+}
+
+/// `json_str!($crate, "...")`
+#[doc(hidden)]
+#[proc_macro]
+pub fn json_str(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let (krate, input) = split_krate(input.into());
+    let (src, span) = match string_literal(input) {
+        Ok(lit) => lit,
+        Err(err) => return err.into(),
+    };
+    match json::parse(&src, span) {
+        Ok(value) => value.expand(&krate).into(),
+        Err(err) => compile_error(&err.describe(&src), span).into(),
+    }
+}
+
+/// `include_json!($crate, "path/to/file.json")`
+#[doc(hidden)]
+#[proc_macro]
+pub fn include_json(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let (krate, input) = split_krate(input.into());
+    let (path, span) = match string_literal(input) {
+        Ok(lit) => lit,
+        Err(err) => return err.into(),
+    };
+
+    let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let path = PathBuf::from(manifest_dir).join(path);
+    let src = match std::fs::read_to_string(&path) {
+        Ok(src) => src,
+        Err(err) => {
+            let msg = format!("couldn't read {}: {}", path.display(), err);
+            return compile_error(&msg, span).into();
+        }
+    };
+
+    let value = match json::parse(&src, span) {
+        Ok(value) => value.expand(&krate),
+        Err(err) => {
+            let msg = format!("{} in {}", err.describe(&src), path.display());
+            return compile_error(&msg, span).into();
+        }
+    };
+
+    // including the file makes cargo rebuild when it changes
+    let path = Literal::string(&path.to_string_lossy());
+    quote!({
+        const _: &[u8] = ::core::include_bytes!(#path);
+        #value
+    })
+    .into()
+}
+
+/// Split the `$crate` path passed in by the wrapping `macro_rules!` from the rest of the input.
+fn split_krate(input: TokenStream) -> (TokenStream, TokenStream) {
+    let mut iter = input.into_iter();
+    let krate = iter
+        .by_ref()
+        .take_while(|tt| !matches!(tt, TokenTree::Punct(p) if p.as_char() == ','))
+        .collect();
+    (krate, iter.collect())
+}
+
+fn compile_error(msg: &str, span: Span) -> TokenStream {
+    quote_spanned!(span=> ::core::compile_error!(#msg))
+}
+
+/// The value of the single string literal in `input`.
+fn string_literal(input: TokenStream) -> Result<(String, Span), TokenStream> {
+    let mut iter = input.into_iter();
+    let tt = match (iter.next(), iter.next()) {
+        // literals passed through `macro_rules!` can be wrapped in an invisible group
+        (Some(TokenTree::Group(g)), None) if g.delimiter() == Delimiter::None => {
+            return string_literal(g.stream())
+        }
+        (Some(tt), None) => tt,
+        (Some(tt), Some(_)) => return Err(compile_error("expected a string literal", tt.span())),
+        (None, _) => {
+            return Err(compile_error(
+                "expected a string literal",
+                Span::call_site(),
+            ))
+        }
+    };
+    let span = tt.span();
+    match &tt {
+        TokenTree::Literal(lit) => match unescape(&lit.to_string()) {
+            Some(s) => Ok((s, span)),
+            None => Err(compile_error("expected a string literal", span)),
+        },
+        _ => Err(compile_error("expected a string literal", span)),
+    }
+}
+
+/// The value of a Rust string literal, given its source text.
+fn unescape(lit: &str) -> Option<String> {
+    if let Some(raw) = lit.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let inner = raw.get(hashes + 1..raw.len() - hashes - 1)?;
+        return Some(inner.to_owned());
+    }
+
+    let inner = lit.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => out.push('\n'),
+            'r' => out.push('\r'),
+            't' => out.push('\t'),
+            '0' => out.push('\0'),
+            '\\' => out.push('\\'),
+            '\'' => out.push('\''),
+            '"' => out.push('"'),
+            'x' => {
+                let hex: String = chars.by_ref().take(2).collect();
+                out.push(u8::from_str_radix(&hex, 16).ok()? as char);
+            }
+            'u' => {
+                let hex: String = chars
+                    .by_ref()
+                    .skip(1)
+                    .take_while(|&c| c != '}')
+                    .filter(|&c| c != '_')
+                    .collect();
+                out.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+            }
+            // line continuation skips the newline and any leading whitespace
+            '\n' => {
+                while chars.peek().map_or(false, |c| c.is_whitespace()) {
+                    chars.next();
+                }
+            }
+            _ => return None,
+        }
+    }
+    Some(out)
+}
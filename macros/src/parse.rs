@@ -0,0 +1,881 @@
+//! Parse the input of `json!` into a [`Value`], accepting the same syntax as the `macro_rules!`
+//! implementation in `typed-json`.
+
+use proc_macro2::{Delimiter, Group, Literal, Spacing, Span, TokenStream, TokenTree};
+use quote::quote;
+
+use crate::case::Case;
+use crate::value::{Element, Entry, Value};
+
+/// An error in the macro input, pointing at the tokens that caused it.
+///
+/// A stable proc macro can't attach a `help` to its error, so the message says how to fix the
+/// input itself, on one line.
+pub struct Error {
+    pub msg: String,
+    pub span: Span,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub fn error<T>(span: Span, msg: impl Into<String>) -> Result<T> {
+    Err(Error {
+        msg: msg.into(),
+        span,
+    })
+}
+
+/// Where a value is, which decides what may follow it.
+#[derive(Clone, Copy)]
+enum Context {
+    /// The whole input
+    Top,
+    /// An element of an array
+    Element,
+    /// The value of an object entry
+    Entry,
+    /// The value of a `match` arm
+    Arm,
+}
+
+/// Parse the whole input of `json!`.
+pub fn parse(input: TokenStream) -> Result<Value> {
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+    value(&tokens, Span::call_site(), Context::Top)
+}
+
+/// A single value. `span` is where to point if it is missing.
+fn value(tokens: &[TokenTree], span: Span, cx: Context) -> Result<Value> {
+    if let Some(i) = find(tokens, |tokens, i| is_punct(&tokens[i], ',')) {
+        return match cx {
+            Context::Top => error(
+                tokens[i].span(),
+                "unexpected `,` after value, wrap several values in `[...]` to make an array",
+            ),
+            _ => error(tokens[i].span(), "unexpected `,`"),
+        };
+    }
+    match tokens.first() {
+        Some(TokenTree::Ident(if_)) if if_ == "if" => return branch(tokens, cx),
+        Some(TokenTree::Ident(match_)) if match_ == "match" => return match_arms(tokens, cx),
+        _ => {}
+    }
+    if let Some(end) = value_end(tokens) {
+        return trailing(&tokens[end..], cx);
+    }
+    match tokens {
+        [] => error(span, "expected a value"),
+        [TokenTree::Ident(null)] if null == "null" => Ok(Value::Null),
+        [TokenTree::Group(g)] if g.delimiter() == Delimiter::Bracket => array(g),
+        [TokenTree::Group(g)] if g.delimiter() == Delimiter::Brace => object(g),
+        [TokenTree::Ident(mac), TokenTree::Punct(bang), TokenTree::Group(args)]
+            if bang.as_char() == '!' && args.delimiter() == Delimiter::Parenthesis =>
+        {
+            if mac == "f" || mac == "format_args" {
+                Ok(Value::Format(args.stream()))
+            } else if mac == "raw" {
+                raw(args)
+            } else {
+                Ok(Value::Expr(tokens.iter().cloned().collect()))
+            }
+        }
+        _ => Ok(Value::Expr(tokens.iter().cloned().collect())),
+    }
+}
+
+/// `raw!("...")`, where a string literal is checked here to point at what is wrong with it.
+/// Anything else is left to the check at compile time that `json!` does too.
+fn raw(args: &Group) -> Result<Value> {
+    if let Some(src) = crate::render::string(&args.stream()) {
+        if let Err(err) = crate::json::parse(&src, args.span()) {
+            return error(
+                args.span(),
+                format!("invalid JSON in `raw!`: {}", err.describe(&src)),
+            );
+        }
+    }
+    Ok(Value::Raw(args.stream()))
+}
+
+/// `if cond { value } else { value }`, where the `else` can be followed by another `if`.
+fn branch(tokens: &[TokenTree], cx: Context) -> Result<Value> {
+    let then = match block(tokens) {
+        Some(then) => then,
+        None => {
+            return error(
+                tokens[0].span(),
+                "expected a condition and then `{ value }`",
+            )
+        }
+    };
+    let cond = tokens[1..then].iter().cloned().collect();
+    let otherwise = match &tokens[then + 1..] {
+        [TokenTree::Ident(else_), TokenTree::Ident(if_), ..] if else_ == "else" && if_ == "if" => {
+            branch(&tokens[then + 2..], cx)?
+        }
+        [TokenTree::Ident(else_), TokenTree::Group(g), rest @ ..]
+            if else_ == "else" && g.delimiter() == Delimiter::Brace =>
+        {
+            if !rest.is_empty() {
+                return trailing(rest, cx);
+            }
+            block_value(g)?
+        }
+        [TokenTree::Ident(else_), ..] if else_ == "else" => {
+            return error(else_.span(), "expected `{ value }` or `if` after `else`")
+        }
+        [] => {
+            return error(
+                tokens[then].span(),
+                "expected `else` after this branch, or `if cond => value` to leave the value \
+                 out of an array or object",
+            )
+        }
+        [tt, ..] => {
+            return error(
+                tt.span(),
+                format!("expected `else`, found {}", describe(tt)),
+            )
+        }
+    };
+    Ok(Value::If {
+        cond,
+        then: Box::new(block_value(match &tokens[then] {
+            TokenTree::Group(g) => g,
+            _ => unreachable!("found by block()"),
+        })?),
+        otherwise: Box::new(otherwise),
+    })
+}
+
+/// `match expr { pat => value, ... }`
+fn match_arms(tokens: &[TokenTree], cx: Context) -> Result<Value> {
+    let body = match block(tokens) {
+        Some(body) => body,
+        None => {
+            return error(
+                tokens[0].span(),
+                "expected an expression and then `{ arms }`",
+            )
+        }
+    };
+    if body + 1 < tokens.len() {
+        return trailing(&tokens[body + 1..], cx);
+    }
+    let group = match &tokens[body] {
+        TokenTree::Group(g) => g,
+        _ => unreachable!("found by block()"),
+    };
+    let inner: Vec<TokenTree> = group.stream().into_iter().collect();
+    let arms = split_commas(&inner)?
+        .into_iter()
+        .map(|arm| {
+            let arrow = match find(arm, is_arrow) {
+                Some(arrow) if arrow > 0 => arrow,
+                _ => return error(arm[0].span(), "expected `pattern => value`"),
+            };
+            let pat = arm[..arrow].iter().cloned().collect();
+            let value = value(&arm[arrow + 2..], arm[arrow + 1].span(), Context::Arm)?;
+            Ok((pat, value))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if arms.is_empty() {
+        return error(group.span(), "expected at least one arm");
+    }
+    Ok(Value::Match {
+        expr: tokens[1..body].iter().cloned().collect(),
+        arms,
+    })
+}
+
+/// The first block after the keyword and at least one token of the expression after it, the
+/// same as where Rust ends the condition of an `if` or the expression of a `match`.
+fn block(tokens: &[TokenTree]) -> Option<usize> {
+    tokens
+        .iter()
+        .skip(2)
+        .position(|tt| matches!(tt, TokenTree::Group(g) if g.delimiter() == Delimiter::Brace))
+        .map(|i| i + 2)
+}
+
+/// The value inside a branch's `{ ... }`.
+fn block_value(group: &Group) -> Result<Value> {
+    let tokens: Vec<TokenTree> = group.stream().into_iter().collect();
+    value(&tokens, group.span(), Context::Top)
+}
+
+fn array(group: &Group) -> Result<Value> {
+    let tokens: Vec<TokenTree> = group.stream().into_iter().collect();
+
+    // `[for pat in iter => value]`
+    if let Some(TokenTree::Ident(for_)) = tokens.first() {
+        if for_ == "for" {
+            let (pat, iter, value_tokens) = comprehension(&tokens[1..], for_.span())?;
+            return Ok(Value::Comprehension {
+                pat,
+                iter,
+                value: Box::new(value(value_tokens, group.span_close(), Context::Top)?),
+            });
+        }
+    }
+
+    let elements = split_commas(&tokens)?
+        .into_iter()
+        .map(element)
+        .collect::<Result<_>>()?;
+    Ok(Value::Array(elements))
+}
+
+fn element(tokens: &[TokenTree]) -> Result<Element> {
+    // `#[attr] element`
+    let attrs = tokens
+        .chunks(2)
+        .take_while(|attr| match attr {
+            [hash, TokenTree::Group(g)] => {
+                is_punct(hash, '#') && g.delimiter() == Delimiter::Bracket
+            }
+            _ => false,
+        })
+        .count();
+    if attrs > 0 {
+        let rest = &tokens[attrs * 2..];
+        if rest.is_empty() {
+            return error(
+                tokens[attrs * 2 - 1].span(),
+                "expected an element after the attribute",
+            );
+        }
+        let attrs = tokens[..attrs * 2].iter().cloned().collect();
+        return Ok(Element::Attrs(attrs, Box::new(element(rest)?)));
+    }
+
+    if let Some(rest) = strip_dot_dot(tokens) {
+        if rest.is_empty() {
+            return error(tokens[1].span(), "expected an iterator to spread");
+        }
+        return Ok(Element::Spread(rest.iter().cloned().collect()));
+    }
+
+    if let Some(TokenTree::Ident(if_)) = tokens.first() {
+        if if_ == "if" {
+            // without a `=>` this is an `if` expression instead
+            if let Some(arrow) = find(tokens, is_arrow) {
+                let cond = condition(&tokens[1..arrow], if_.span())?;
+                let value = value(
+                    &tokens[arrow + 2..],
+                    tokens[arrow + 1].span(),
+                    Context::Element,
+                )?;
+                return Ok(Element::If(cond, value));
+            }
+        }
+    }
+
+    value(tokens, Span::call_site(), Context::Element).map(Element::Value)
+}
+
+fn object(group: &Group) -> Result<Value> {
+    let tokens: Vec<TokenTree> = group.stream().into_iter().collect();
+    let mut tokens = &tokens[..];
+
+    let mut case = Case::None;
+    let mut sort = false;
+    while let [TokenTree::Punct(hash), TokenTree::Punct(bang), TokenTree::Group(attr), rest @ ..] =
+        tokens
+    {
+        if hash.as_char() != '#' || bang.as_char() != '!' {
+            break;
+        }
+        match attr.stream().into_iter().next() {
+            Some(TokenTree::Ident(name))
+                if name == "sort_keys" && attr.stream().into_iter().count() == 1 =>
+            {
+                sort = true;
+            }
+            _ => case = rename_all(attr)?,
+        }
+        tokens = rest;
+    }
+
+    let mut object = Object {
+        case,
+        seen: Vec::new(),
+    };
+    let entries = split_commas(tokens)?
+        .into_iter()
+        .map(|entry| Ok((object.entry(entry)?, entry[0].span())))
+        .collect::<Result<Vec<_>>>()?;
+    if sort {
+        return sort_keys(entries).map(Value::Object);
+    }
+    Ok(Value::Object(
+        entries.into_iter().map(|(entry, _)| entry).collect(),
+    ))
+}
+
+/// `rename_all = "..."`
+fn rename_all(attr: &Group) -> Result<Case> {
+    let tokens: Vec<TokenTree> = attr.stream().into_iter().collect();
+    match &tokens[..] {
+        [TokenTree::Ident(name), TokenTree::Punct(eq), TokenTree::Literal(case)]
+            if name == "rename_all" && eq.as_char() == '=' =>
+        {
+            let name = crate::unescape(&case.to_string()).unwrap_or_default();
+            match Case::from_name(&name) {
+                Some(case) => Ok(case),
+                None => error(case.span(), format!("unknown case `{}`", name)),
+            }
+        }
+        _ => error(
+            attr.span(),
+            "expected `rename_all = \"...\"` or `sort_keys`",
+        ),
+    }
+}
+
+/// `#![sort_keys]`, which orders the entries of an object by their keys as UTF-16 code units,
+/// the same as canonical JSON. Every key has to be known at compile time.
+pub fn sort_keys(entries: Vec<(Entry, Span)>) -> Result<Vec<Entry>> {
+    let mut keyed = Vec::with_capacity(entries.len());
+    for (entry, span) in entries {
+        match entry_key(&entry) {
+            Some(key) => keyed.push((key.encode_utf16().collect::<Vec<u16>>(), entry)),
+            None => {
+                return error(
+                    span,
+                    "`#![sort_keys]` needs a literal key for every entry, sort the object at \
+                     runtime with `typed_json::to_canonical_string` instead",
+                )
+            }
+        }
+    }
+    // stable, so that conditional entries with the same key stay in order
+    keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(keyed.into_iter().map(|(_, entry)| entry).collect())
+}
+
+/// The literal key of an entry, if there is one.
+fn entry_key(entry: &Entry) -> Option<String> {
+    match entry {
+        Entry::Pair(Value::Expr(key), _) | Entry::Optional(Value::Expr(key), _) => {
+            literal_key(&key.clone().into_iter().collect::<Vec<_>>())
+        }
+        Entry::If(_, entry) | Entry::Attrs(_, entry) => entry_key(entry),
+        _ => None,
+    }
+}
+
+/// The state of an object while parsing its entries.
+struct Object {
+    case: Case,
+    /// The literal keys of the entries so far.
+    seen: Vec<String>,
+}
+
+impl Object {
+    fn entry(&mut self, tokens: &[TokenTree]) -> Result<Entry> {
+        // `#[attr] entry`
+        let attrs = tokens
+            .chunks(2)
+            .take_while(|attr| match attr {
+                [hash, TokenTree::Group(g)] => {
+                    is_punct(hash, '#') && g.delimiter() == Delimiter::Bracket
+                }
+                _ => false,
+            })
+            .count();
+        if attrs > 0 {
+            let rest = &tokens[attrs * 2..];
+            if rest.is_empty() {
+                return error(
+                    tokens[attrs * 2 - 1].span(),
+                    "expected an entry after the attribute",
+                );
+            }
+            // the entry may be compiled out, so like a conditional entry its key is not remembered
+            let seen = self.seen.len();
+            let entry = self.entry(rest)?;
+            self.seen.truncate(seen);
+            let attrs = tokens[..attrs * 2].iter().cloned().collect();
+            return Ok(Entry::Attrs(attrs, Box::new(entry)));
+        }
+
+        // `..value`
+        if let Some(rest) = strip_dot_dot(tokens) {
+            return match rest {
+                [] => error(tokens[1].span(), "expected an object to spread"),
+                [TokenTree::Group(g)] if g.delimiter() == Delimiter::Brace => {
+                    Ok(Entry::Spread(object(g)?))
+                }
+                _ => Ok(Entry::Spread(Value::Expr(rest.iter().cloned().collect()))),
+            };
+        }
+
+        match tokens.first() {
+            // `for pat in iter => key: value`
+            Some(TokenTree::Ident(for_)) if for_ == "for" => {
+                let (pat, iter, rest) = comprehension(&tokens[1..], for_.span())?;
+                let (key, value) = match self.pair(rest)? {
+                    // shorthand would repeat the same key for every item
+                    (Entry::Pair(key, value), _) if !matches!(rest, [TokenTree::Ident(_)]) => {
+                        (key, value)
+                    }
+                    _ => return error(for_.span(), "expected `key: value` after `=>`"),
+                };
+                Ok(Entry::For {
+                    pat,
+                    iter,
+                    key,
+                    value,
+                })
+            }
+            // `if cond => entry`, unless the colon comes first and the key is an `if` expression
+            Some(TokenTree::Ident(if_)) if if_ == "if" => {
+                let arrow = find(tokens, is_arrow);
+                let colon = find(tokens, |tokens, i| is_punct(&tokens[i], ':'));
+                match (arrow, colon) {
+                    (Some(arrow), colon) if colon.map_or(true, |colon| arrow < colon) => {
+                        let cond = condition(&tokens[1..arrow], if_.span())?;
+                        // conditions may be mutually exclusive, so the key is not remembered
+                        let (entry, key) = self.pair(&tokens[arrow + 2..])?;
+                        if let Some((key, span)) = key {
+                            self.check(&key, span)?;
+                        }
+                        Ok(Entry::If(cond, Box::new(entry)))
+                    }
+                    _ => self.insert(tokens),
+                }
+            }
+            _ => self.insert(tokens),
+        }
+    }
+
+    /// An entry whose key is checked against, and then added to, the keys seen so far.
+    fn insert(&mut self, tokens: &[TokenTree]) -> Result<Entry> {
+        let (entry, key) = self.pair(tokens)?;
+        if let Some((key, span)) = key {
+            self.check(&key, span)?;
+            self.seen.push(key);
+        }
+        Ok(entry)
+    }
+
+    fn check(&self, key: &str, span: Span) -> Result<()> {
+        if self.seen.iter().any(|seen| seen == key) {
+            return error(span, format!("duplicate key `{}`", key));
+        }
+        Ok(())
+    }
+
+    /// `key: value`, `key?: value` or the shorthand `name`, along with the literal key if it is
+    /// known at compile time.
+    fn pair(&self, tokens: &[TokenTree]) -> Result<(Entry, Option<(String, Span)>)> {
+        if let [TokenTree::Ident(name)] = tokens {
+            let key = self.case.convert(&name.to_string());
+            let mut lit = Literal::string(&key);
+            lit.set_span(name.span());
+            let entry = Entry::Pair(Value::Expr(quote!(#lit)), Value::Expr(quote!(#name)));
+            return Ok((entry, Some((key, name.span()))));
+        }
+
+        let colon = match find(tokens, |tokens, i| is_punct(&tokens[i], ':')) {
+            Some(colon) => colon,
+            None => return missing_colon(tokens),
+        };
+        let colon_span = tokens[colon].span();
+        let (key_tokens, optional) = match &tokens[..colon] {
+            [key @ .., TokenTree::Punct(q)] if q.as_char() == '?' => (key, true),
+            key => (key, false),
+        };
+        if key_tokens.is_empty() {
+            return error(colon_span, "expected a key");
+        }
+        check_key(key_tokens, colon_span)?;
+
+        let name = literal_key(key_tokens).map(|name| (name, key_tokens[0].span()));
+        let key = match key_tokens {
+            // a parenthesized key is an expression
+            [TokenTree::Group(g)] if g.delimiter() == Delimiter::Parenthesis => {
+                let inner: Vec<TokenTree> = g.stream().into_iter().collect();
+                match find(&inner, |tokens, i| is_punct(&tokens[i], ',')) {
+                    None => Value::Expr(g.stream()),
+                    // a tuple
+                    Some(_) => Value::Expr(quote!(#g)),
+                }
+            }
+            _ => value(key_tokens, colon_span, Context::Top)?,
+        };
+        let entry = self.value_for(key, &tokens[colon + 1..], optional, colon_span)?;
+        Ok((entry, name))
+    }
+
+    fn value_for(
+        &self,
+        key: Value,
+        tokens: &[TokenTree],
+        optional: bool,
+        colon_span: Span,
+    ) -> Result<Entry> {
+        // another `:` means the next entry started without a `,`
+        if let Some(colon) = find(tokens, |tokens, i| is_punct(&tokens[i], ':')) {
+            let next = value_end(&tokens[..colon]).unwrap_or_else(|| next_key(tokens, colon));
+            if next == 0 {
+                return error(
+                    tokens[colon].span(),
+                    "expected a value, found another key, wrap the entries of a nested object \
+                     in `{ ... }`",
+                );
+            }
+            return trailing(&tokens[next..], Context::Entry);
+        }
+
+        if !optional {
+            return Ok(Entry::Pair(key, value(tokens, colon_span, Context::Entry)?));
+        }
+        if tokens.is_empty() {
+            return error(colon_span, "expected a value");
+        }
+        if let Some(end) = boundary(tokens) {
+            return trailing(&tokens[end..], Context::Entry);
+        }
+        Ok(Entry::Optional(key, tokens.iter().cloned().collect()))
+    }
+}
+
+/// An entry with no `:`.
+fn missing_colon<T>(tokens: &[TokenTree]) -> Result<T> {
+    if let Some(eq) = find(tokens, |tokens, i| is_punct(&tokens[i], '=')) {
+        let found = if is_arrow(tokens, eq) { "=>" } else { "=" };
+        return error(
+            tokens[eq].span(),
+            format!(
+                "expected `:` between the key and its value, found `{}`",
+                found
+            ),
+        );
+    }
+    if let Some(end) = value_end(tokens) {
+        return error(
+            tokens[end].span(),
+            format!(
+                "expected `:` between the key and its value, found {}",
+                describe(&tokens[end])
+            ),
+        );
+    }
+    let last = &tokens[tokens.len() - 1];
+    error(
+        last.span(),
+        "expected `: value` after this key, only a variable name can be used on its own",
+    )
+}
+
+/// Reject keys that end in the middle of an expression, because the `:` after them belongs to
+/// that expression: a label, the parameters of a closure, or a missing operand.
+fn check_key(key: &[TokenTree], colon_span: Span) -> Result<()> {
+    let label = is_joint(&key[0], '\'');
+    let closure = match key {
+        [TokenTree::Ident(move_), pipe, ..] if move_ == "move" => is_punct(pipe, '|'),
+        [pipe, ..] => is_punct(pipe, '|'),
+        [] => false,
+    };
+    if label || closure {
+        return error(
+            colon_span,
+            "this `:` is part of the key expression, wrap the key in parentheses like \
+             `(key): value`",
+        );
+    }
+    if let TokenTree::Punct(op) = &key[key.len() - 1] {
+        return error(
+            colon_span,
+            format!(
+                "expected an expression after `{}` to finish the key, found `:`",
+                op.as_char()
+            ),
+        );
+    }
+    Ok(())
+}
+
+/// The error for tokens after a complete value.
+fn trailing<T>(rest: &[TokenTree], cx: Context) -> Result<T> {
+    let tt = &rest[0];
+    let starts_value = match tt {
+        TokenTree::Ident(ident) => !is_keyword(ident),
+        TokenTree::Literal(_) | TokenTree::Group(_) => true,
+        TokenTree::Punct(_) => false,
+    };
+    match cx {
+        Context::Element if starts_value => error(
+            tt.span(),
+            format!(
+                "expected `,` between the elements of an array, found {}",
+                describe(tt)
+            ),
+        ),
+        Context::Entry if starts_value => error(
+            tt.span(),
+            format!(
+                "expected `,` between the entries of an object, found {}",
+                describe(tt)
+            ),
+        ),
+        Context::Arm if starts_value => error(
+            tt.span(),
+            format!(
+                "expected `,` between the arms of a `match`, found {}",
+                describe(tt)
+            ),
+        ),
+        Context::Top if starts_value => error(
+            tt.span(),
+            format!(
+                "unexpected {} after value, wrap several values in `[...]` to make an array",
+                describe(tt)
+            ),
+        ),
+        _ => error(
+            tt.span(),
+            format!(
+                "unexpected {} after value, wrap it all in parentheses to use the value in a \
+                 Rust expression",
+                describe(tt)
+            ),
+        ),
+    }
+}
+
+/// Where the value at the start of `tokens` ends, if any tokens follow it.
+fn value_end(tokens: &[TokenTree]) -> Option<usize> {
+    // these are always a whole value, like in the `macro_rules!` implementation
+    let len = match tokens {
+        [TokenTree::Ident(i), ..] if i == "null" || i == "true" || i == "false" => 1,
+        [TokenTree::Group(g), ..]
+            if g.delimiter() == Delimiter::Bracket || g.delimiter() == Delimiter::Brace =>
+        {
+            1
+        }
+        [TokenTree::Ident(mac), TokenTree::Punct(bang), TokenTree::Group(args), ..]
+            if (mac == "f" || mac == "format_args" || mac == "raw")
+                && bang.as_char() == '!'
+                && args.delimiter() == Delimiter::Parenthesis =>
+        {
+            3
+        }
+        _ => return boundary(tokens),
+    };
+    Some(len).filter(|&len| len < tokens.len())
+}
+
+/// The first token that starts a new value straight after another one, such as the `2` in
+/// `1 2`, which is never valid Rust.
+fn boundary(tokens: &[TokenTree]) -> Option<usize> {
+    let top = top_level(tokens);
+    top.windows(2).find_map(|w| {
+        let (i, j) = (w[0], w[1]);
+        if j != i + 1 {
+            return None;
+        }
+        let ends = match &tokens[i] {
+            // `'label`
+            TokenTree::Ident(_) if i > 0 && is_joint(&tokens[i - 1], '\'') => false,
+            TokenTree::Ident(ident) => !is_keyword(ident),
+            TokenTree::Literal(_) | TokenTree::Group(_) => true,
+            TokenTree::Punct(_) => false,
+        };
+        let starts = match &tokens[j] {
+            TokenTree::Ident(ident) => !is_keyword(ident),
+            TokenTree::Literal(_) => true,
+            // `Struct { .. }`, `call(..)` and `index[..]` continue an expression
+            TokenTree::Group(g) => match g.delimiter() {
+                Delimiter::Brace => !matches!(tokens[i], TokenTree::Ident(_)),
+                Delimiter::Parenthesis | Delimiter::Bracket => {
+                    matches!(tokens[i], TokenTree::Literal(_))
+                }
+                Delimiter::None => true,
+            },
+            TokenTree::Punct(_) => false,
+        };
+        Some(j).filter(|_| ends && starts)
+    })
+}
+
+/// Where the key before the `:` at `colon` starts, guessing that it is a single token.
+fn next_key(tokens: &[TokenTree], colon: usize) -> usize {
+    let mut start = colon;
+    if start > 0 && is_punct(&tokens[start - 1], '?') {
+        start -= 1;
+    }
+    match &tokens[..start] {
+        [.., TokenTree::Ident(_), TokenTree::Punct(bang), TokenTree::Group(_)]
+            if bang.as_char() == '!' =>
+        {
+            start - 3
+        }
+        _ => start.saturating_sub(1),
+    }
+}
+
+/// Keywords that can't start or end an expression next to another one, so don't mark the
+/// boundary between two values.
+fn is_keyword(ident: &proc_macro2::Ident) -> bool {
+    const KEYWORDS: &[&str] = &[
+        "as", "async", "await", "box", "break", "const", "continue", "dyn", "else", "enum",
+        "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+        "pub", "ref", "return", "static", "struct", "trait", "type", "unsafe", "use", "where",
+        "while", "yield",
+    ];
+    KEYWORDS.iter().any(|keyword| ident == keyword)
+}
+
+/// A token as it would be quoted in an error.
+pub fn describe(tt: &TokenTree) -> String {
+    match tt {
+        TokenTree::Group(g) => match g.delimiter() {
+            Delimiter::Parenthesis => "`(...)`".to_owned(),
+            Delimiter::Bracket => "`[...]`".to_owned(),
+            Delimiter::Brace => "`{...}`".to_owned(),
+            Delimiter::None => format!("`{}`", g.stream()),
+        },
+        tt => format!("`{}`", tt),
+    }
+}
+
+/// `pat in iter => rest`, following a `for`.
+fn comprehension(
+    tokens: &[TokenTree],
+    span: Span,
+) -> Result<(TokenStream, TokenStream, &[TokenTree])> {
+    let in_ = find(
+        tokens,
+        |tokens, i| matches!(&tokens[i], TokenTree::Ident(i) if i == "in"),
+    );
+    let in_ = match in_ {
+        Some(in_) if in_ > 0 => in_,
+        _ => return error(span, "expected `for pattern in iterator =>`"),
+    };
+    let arrow = match find(&tokens[in_..], is_arrow) {
+        Some(arrow) if arrow > 1 => in_ + arrow,
+        _ => return error(tokens[in_].span(), "expected `in iterator =>`"),
+    };
+    let pat = tokens[..in_].iter().cloned().collect();
+    let iter = tokens[in_ + 1..arrow].iter().cloned().collect();
+    Ok((pat, iter, &tokens[arrow + 2..]))
+}
+
+fn condition(tokens: &[TokenTree], span: Span) -> Result<TokenStream> {
+    if tokens.is_empty() {
+        return error(span, "expected a condition");
+    }
+    Ok(tokens.iter().cloned().collect())
+}
+
+/// The value of a literal key, the same as `concat!` would give for it.
+fn literal_key(tokens: &[TokenTree]) -> Option<String> {
+    let lit = match tokens {
+        [TokenTree::Literal(lit)] => lit.to_string(),
+        _ => return None,
+    };
+    if lit.starts_with('"') || lit.starts_with('r') {
+        crate::unescape(&lit)
+    } else if let Some(c) = lit.strip_prefix('\'').and_then(|c| c.strip_suffix('\'')) {
+        if c.starts_with('\\') {
+            crate::unescape(&format!("\"{}\"", c))
+        } else {
+            Some(c.to_owned())
+        }
+    } else {
+        // only decimal integers, anything else is not checked
+        let end = lit
+            .find(|c: char| !c.is_ascii_digit() && c != '_')
+            .unwrap_or(lit.len());
+        let (digits, suffix) = lit.split_at(end);
+        const INTEGERS: &[&str] = &[
+            "", "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128",
+            "usize",
+        ];
+        if !INTEGERS.contains(&suffix) {
+            return None;
+        }
+        let value: u128 = digits.replace('_', "").parse().ok()?;
+        Some(value.to_string())
+    }
+}
+
+/// Split on the commas between elements or entries. A trailing comma is allowed.
+fn split_commas(tokens: &[TokenTree]) -> Result<Vec<&[TokenTree]>> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for i in top_level(tokens) {
+        if is_punct(&tokens[i], ',') {
+            if i == start {
+                return error(tokens[i].span(), "unexpected `,`");
+            }
+            parts.push(&tokens[start..i]);
+            start = i + 1;
+        }
+    }
+    if start < tokens.len() {
+        parts.push(&tokens[start..]);
+    }
+    Ok(parts)
+}
+
+/// The position of the first token that matches, outside of `::` and turbofish generics.
+fn find(tokens: &[TokenTree], f: impl Fn(&[TokenTree], usize) -> bool) -> Option<usize> {
+    top_level(tokens).into_iter().find(|&i| f(tokens, i))
+}
+
+/// The positions of the tokens that can separate the parts of an entry or element. This skips
+/// `::` and the arguments of a turbofish `::<A, B>`, whose `:` and `,` are part of an
+/// expression. Any other `,` or `:` ends an expression.
+fn top_level(tokens: &[TokenTree]) -> Vec<usize> {
+    let mut out = Vec::with_capacity(tokens.len());
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < tokens.len() {
+        if is_joint(&tokens[i], ':') && tokens.get(i + 1).map_or(false, |tt| is_punct(tt, ':')) {
+            i += 2;
+            if depth == 0 && tokens.get(i).map_or(false, |tt| is_punct(tt, '<')) {
+                depth = 1;
+                i += 1;
+            }
+            continue;
+        }
+        if depth > 0 {
+            if is_punct(&tokens[i], '<') {
+                depth += 1;
+            } else if is_punct(&tokens[i], '>') && !(i > 0 && is_joint(&tokens[i - 1], '-')) {
+                depth -= 1;
+            }
+        } else {
+            out.push(i);
+        }
+        i += 1;
+    }
+    out
+}
+
+/// `..` at the start of an element or entry, returning the rest.
+pub fn strip_dot_dot(tokens: &[TokenTree]) -> Option<&[TokenTree]> {
+    match tokens {
+        [dot, TokenTree::Punct(p), rest @ ..] if is_joint(dot, '.') && p.as_char() == '.' => {
+            Some(rest)
+        }
+        _ => None,
+    }
+}
+
+/// `=>` at position `i`.
+fn is_arrow(tokens: &[TokenTree], i: usize) -> bool {
+    is_joint(&tokens[i], '=') && tokens.get(i + 1).map_or(false, |tt| is_punct(tt, '>'))
+}
+
+pub fn is_punct(tt: &TokenTree, c: char) -> bool {
+    matches!(tt, TokenTree::Punct(p) if p.as_char() == c)
+}
+
+pub fn is_joint(tt: &TokenTree, c: char) -> bool {
+    matches!(tt, TokenTree::Punct(p) if p.as_char() == c && p.spacing() == Spacing::Joint)
+}
@@ -0,0 +1,274 @@
+//! Render a [`Value`] made only of literals to JSON at compile time, the same as `serde_json`
+//! would write it at runtime.
+
+use proc_macro2::{Delimiter, Span, TokenStream, TokenTree};
+
+use crate::value::{Element, Entry, Value};
+
+/// The JSON for a value, or where the first part of it that is only known at runtime is, and
+/// why.
+pub type Result<T> = std::result::Result<T, (Span, &'static str)>;
+
+const RUNTIME: &str = "expected a literal, this is only known at runtime";
+const KEY: &str = "expected a string literal key";
+const ATTRS: &str = "an entry or element with attributes can't be rendered at compile time";
+
+/// How to render a value.
+#[derive(Clone, Copy)]
+pub struct Render {
+    /// Whether to render float literals. `serde_json` has changed how it formats floats between
+    /// versions, so these can only match the same number, not the exact text.
+    pub floats: bool,
+}
+
+impl Render {
+    /// The compact JSON, the same as `serde_json::to_string`.
+    pub fn compact(self, value: &Value) -> Result<String> {
+        let mut out = String::new();
+        self.write(value, &mut out, None)?;
+        Ok(out)
+    }
+
+    /// The pretty JSON, the same as `serde_json::to_string_pretty`.
+    pub fn pretty(self, value: &Value) -> Result<String> {
+        let mut out = String::new();
+        self.write(value, &mut out, Some(0))?;
+        Ok(out)
+    }
+
+    /// Write `value` to `out`, indented to `level` if pretty printing.
+    fn write(self, value: &Value, out: &mut String, level: Option<usize>) -> Result<()> {
+        match value {
+            Value::Null => out.push_str("null"),
+            Value::Expr(expr) => {
+                out.push_str(&leaf(expr, self.floats).ok_or_else(|| runtime(expr))?)
+            }
+            // already JSON, which `serde_json` writes as is
+            Value::Raw(json) => out.push_str(&string(json).ok_or_else(|| runtime(json))?),
+            Value::Format(args) => return Err(runtime(args)),
+            Value::Comprehension { pat, .. } => return Err(runtime(pat)),
+            Value::If { cond, .. } => return Err(runtime(cond)),
+            Value::Match { expr, .. } => return Err(runtime(expr)),
+            Value::Array(elements) => {
+                let mut values = Vec::with_capacity(elements.len());
+                flatten(elements, &mut values)?;
+                collection(out, level, '[', ']', values, |value, out, level| {
+                    self.write(value, out, level)
+                })?;
+            }
+            Value::Object(entries) => {
+                let entries = entries
+                    .iter()
+                    .map(|entry| match entry {
+                        Entry::Pair(Value::Expr(key), value) => match string(key) {
+                            Some(key) => Ok((key, value)),
+                            None => Err((span(key), KEY)),
+                        },
+                        Entry::Pair(key, _) => Err((value_span(key), KEY)),
+                        Entry::Optional(_, value) => Err(runtime(value)),
+                        Entry::If(cond, _) => Err(runtime(cond)),
+                        Entry::Attrs(attrs, _) => Err((span(attrs), ATTRS)),
+                        Entry::Spread(value) => Err((value_span(value), RUNTIME)),
+                        Entry::For { pat, .. } => Err(runtime(pat)),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                collection(out, level, '{', '}', entries, |(key, value), out, level| {
+                    out.push_str(&escape(&key));
+                    out.push_str(if level.is_some() { ": " } else { ":" });
+                    self.write(value, out, level)
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The values of an array, which can't be conditional or spread.
+fn flatten<'a>(elements: &'a [Element], values: &mut Vec<&'a Value>) -> Result<()> {
+    for element in elements {
+        match element {
+            Element::Value(value) => values.push(value),
+            Element::If(cond, _) => return Err(runtime(cond)),
+            Element::Spread(iter) => return Err(runtime(iter)),
+            Element::Attrs(attrs, _) => return Err((span(attrs), ATTRS)),
+        }
+    }
+    Ok(())
+}
+
+/// An array or object, laid out the same way as `serde_json`'s `PrettyFormatter` if `level`
+/// is set.
+fn collection<T>(
+    out: &mut String,
+    level: Option<usize>,
+    open: char,
+    close: char,
+    items: Vec<T>,
+    mut item: impl FnMut(T, &mut String, Option<usize>) -> Result<()>,
+) -> Result<()> {
+    out.push(open);
+    let empty = items.is_empty();
+    for (i, value) in items.into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if let Some(level) = level {
+            out.push('\n');
+            indent(out, level + 1);
+        }
+        item(value, out, level.map(|level| level + 1))?;
+    }
+    if let (Some(level), false) = (level, empty) {
+        out.push('\n');
+        indent(out, level);
+    }
+    out.push(close);
+    Ok(())
+}
+
+fn indent(out: &mut String, level: usize) {
+    for _ in 0..level {
+        out.push_str("  ");
+    }
+}
+
+/// Where to point at a value that can't be rendered.
+fn value_span(value: &Value) -> Span {
+    match value {
+        Value::Expr(tokens) | Value::Format(tokens) | Value::Raw(tokens) => span(tokens),
+        _ => Span::call_site(),
+    }
+}
+
+fn runtime(tokens: &TokenStream) -> (Span, &'static str) {
+    (span(tokens), RUNTIME)
+}
+
+fn span(tokens: &TokenStream) -> Span {
+    tokens
+        .clone()
+        .into_iter()
+        .next()
+        .map_or_else(Span::call_site, |tt| tt.span())
+}
+
+/// `true`, `false`, or a string, char, integer or float literal.
+fn leaf(expr: &TokenStream, floats: bool) -> Option<String> {
+    match tokens(expr).as_slice() {
+        [TokenTree::Ident(b)] if b == "true" || b == "false" => Some(b.to_string()),
+        [TokenTree::Literal(lit)] => {
+            let lit = lit.to_string();
+            match lit.as_bytes()[0] {
+                b'"' | b'r' => crate::unescape(&lit).map(|s| escape(&s)),
+                b'\'' => char(&lit).map(|c| escape(c.encode_utf8(&mut [0; 4]))),
+                _ => number(&lit, false, floats),
+            }
+        }
+        [TokenTree::Punct(minus), TokenTree::Literal(lit)] if minus.as_char() == '-' => {
+            number(&lit.to_string(), true, floats)
+        }
+        _ => None,
+    }
+}
+
+/// The value of a single string literal.
+pub fn string(expr: &TokenStream) -> Option<String> {
+    match tokens(expr).as_slice() {
+        [TokenTree::Literal(lit)] => {
+            let lit = lit.to_string();
+            match lit.as_bytes()[0] {
+                b'"' | b'r' => crate::unescape(&lit),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The tokens of `expr`, without the invisible groups that `macro_rules!` can wrap them in.
+fn tokens(expr: &TokenStream) -> Vec<TokenTree> {
+    let tokens: Vec<TokenTree> = expr.clone().into_iter().collect();
+    match tokens.as_slice() {
+        [TokenTree::Group(g)] if g.delimiter() == Delimiter::None => self::tokens(&g.stream()),
+        _ => tokens,
+    }
+}
+
+fn char(lit: &str) -> Option<char> {
+    let inner = lit.strip_prefix('\'')?.strip_suffix('\'')?;
+    // the only character that needs escaping in a string but not in a char
+    if inner == "\"" {
+        return Some('"');
+    }
+    let s = crate::unescape(&format!("\"{}\"", inner))?;
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// An integer literal, with any radix or suffix, in decimal, or a float literal in the shortest
+/// form that reads back as the same number.
+fn number(lit: &str, negative: bool, floats: bool) -> Option<String> {
+    const SUFFIXES: [&str; 12] = [
+        "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+    ];
+    let lit = lit.replace('_', "");
+    let (radix, digits) = match lit.get(..2) {
+        Some("0x") => (16, &lit[2..]),
+        Some("0o") => (8, &lit[2..]),
+        Some("0b") => (2, &lit[2..]),
+        _ => (10, &lit[..]),
+    };
+    let digits = SUFFIXES
+        .iter()
+        .find_map(|suffix| digits.strip_suffix(suffix))
+        .unwrap_or(digits);
+    if let Ok(n) = u128::from_str_radix(digits, radix) {
+        return Some(if negative && n != 0 {
+            format!("-{}", n)
+        } else {
+            n.to_string()
+        });
+    }
+    if radix != 10 || !floats {
+        return None;
+    }
+
+    let sign = if negative { "-" } else { "" };
+    let mut buffer = ryu::Buffer::new();
+    let float = match lit.strip_suffix("f32") {
+        Some(lit) => {
+            let f = format!("{}{}", sign, lit).parse::<f32>().ok()?;
+            f.is_finite().then(|| buffer.format_finite(f))
+        }
+        None => {
+            let lit = lit.strip_suffix("f64").unwrap_or(&lit);
+            let f = format!("{}{}", sign, lit).parse::<f64>().ok()?;
+            f.is_finite().then(|| buffer.format_finite(f))
+        }
+    };
+    float.map(str::to_owned)
+}
+
+/// A JSON string, escaped the same way as `serde_json`.
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
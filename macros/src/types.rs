@@ -0,0 +1,363 @@
+//! Parse the template of `json_type!`, and spell out the type that `json!` produces for it.
+//!
+//! A template is written like the `json!` literal it describes, with each interpolated
+//! expression replaced by its type. Literals stay literals, and get the type they have in
+//! `json!`.
+
+use proc_macro2::{Delimiter, Group, Ident, Span, TokenStream, TokenTree};
+use quote::quote;
+
+use crate::parse::{describe, error, is_joint, is_punct, sort_keys, strip_dot_dot, Result};
+use crate::value::{hlist, Element, Entry, Value};
+
+/// Parse the whole template. Each hole is a [`Value::Expr`] of its type.
+pub fn parse(input: TokenStream) -> Result<Value> {
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+    value(&tokens, Span::call_site())
+}
+
+/// A single value. `span` is where to point if it is missing.
+fn value(tokens: &[TokenTree], span: Span) -> Result<Value> {
+    match tokens {
+        [] => error(span, "expected a type"),
+        [TokenTree::Ident(null)] if null == "null" => Ok(Value::Null),
+        [TokenTree::Group(g)] if g.delimiter() == Delimiter::Bracket => array(g),
+        [TokenTree::Group(g)] if g.delimiter() == Delimiter::Brace => object(g),
+        [TokenTree::Ident(mac), TokenTree::Punct(bang), TokenTree::Group(args)]
+            if bang.as_char() == '!' && (mac == "f" || mac == "format_args") =>
+        {
+            error(
+                mac.span(),
+                "`f!` has a type that can't be named, format into a `String` and use `String` \
+                 instead",
+            )
+        }
+        [TokenTree::Ident(branch), ..] if branch == "if" || branch == "match" => error(
+            branch.span(),
+            format!(
+                "expected a type, found `{}`, a branch is an `Either` of the types of its \
+                 values like `typed_json::Either<json_type!(..), json_type!(..)>`",
+                branch
+            ),
+        ),
+        [TokenTree::Ident(raw), TokenTree::Punct(bang), TokenTree::Group(args)]
+            if bang.as_char() == '!' && raw == "raw" =>
+        {
+            Ok(Value::Raw(args.stream()))
+        }
+        [TokenTree::Literal(lit)] if lit.to_string().starts_with("b\"") => error(
+            lit.span(),
+            "byte string literals are not supported in `json_type!`, write their type instead \
+             like `&'static [u8; 3]`",
+        ),
+        _ => {
+            if let Some(comma) = top_level(tokens).find(|&i| is_punct(&tokens[i], ',')) {
+                return error(tokens[comma].span(), "unexpected `,`");
+            }
+            Ok(Value::Expr(tokens.iter().cloned().collect()))
+        }
+    }
+}
+
+fn array(group: &Group) -> Result<Value> {
+    let tokens: Vec<TokenTree> = group.stream().into_iter().collect();
+
+    if let Some(TokenTree::Ident(for_)) = tokens.first() {
+        if for_ == "for" {
+            return error(
+                for_.span(),
+                "a comprehension has a type that can't be named, collect the items into a `Vec` \
+                 and use `Vec<T>` instead",
+            );
+        }
+    }
+
+    // `[T; N]`
+    if let Some(semi) = top_level(&tokens).find(|&i| is_punct(&tokens[i], ';')) {
+        let len = match &tokens[semi + 1..] {
+            [TokenTree::Literal(len)] => len.to_string().trim_end_matches("usize").parse().ok(),
+            _ => None,
+        };
+        let len: usize = match len {
+            Some(len) => len,
+            None => return error(tokens[semi].span(), "expected a length after `;`"),
+        };
+        let mut elements = Vec::with_capacity(len);
+        for _ in 0..len {
+            elements.push(Element::Value(value(&tokens[..semi], tokens[semi].span())?));
+        }
+        return Ok(Value::Array(elements));
+    }
+
+    let elements = split_commas(&tokens)?
+        .into_iter()
+        .map(element)
+        .collect::<Result<_>>()?;
+    Ok(Value::Array(elements))
+}
+
+/// `T` or `..I`, after any `#[attr]`s.
+fn element(tokens: &[TokenTree]) -> Result<Element> {
+    if let [hash, TokenTree::Group(attr), rest @ ..] = tokens {
+        if is_punct(hash, '#') && attr.delimiter() == Delimiter::Bracket {
+            if rest.is_empty() {
+                return error(attr.span(), "expected an element after the attribute");
+            }
+            return Ok(Element::Attrs(
+                quote!(#hash #attr),
+                Box::new(element(rest)?),
+            ));
+        }
+    }
+    match strip_dot_dot(tokens) {
+        Some([]) => error(
+            tokens[1].span(),
+            "expected the type of an iterator to spread",
+        ),
+        Some(rest) => Ok(Element::Spread(rest.iter().cloned().collect())),
+        None => value(tokens, Span::call_site()).map(Element::Value),
+    }
+}
+
+fn object(group: &Group) -> Result<Value> {
+    let tokens: Vec<TokenTree> = group.stream().into_iter().collect();
+
+    // `#![sort_keys]`, to describe the same order as `json!`
+    if let [hash, bang, TokenTree::Group(attr), rest @ ..] = &tokens[..] {
+        if is_punct(hash, '#') && is_punct(bang, '!') {
+            if attr.stream().to_string() != "sort_keys" {
+                return error(attr.span(), "expected `sort_keys`");
+            }
+            let entries = split_commas(rest)?
+                .into_iter()
+                .map(|tokens| Ok((entry(tokens)?, tokens[0].span())))
+                .collect::<Result<_>>()?;
+            return sort_keys(entries).map(Value::Object);
+        }
+    }
+
+    let entries = split_commas(&tokens)?
+        .into_iter()
+        .map(entry)
+        .collect::<Result<_>>()?;
+    Ok(Value::Object(entries))
+}
+
+/// `"key": T`, `"key"?: T`, `(K): T` or `..T`, after any `#[attr]`s.
+fn entry(tokens: &[TokenTree]) -> Result<Entry> {
+    if let [hash, TokenTree::Group(attr), rest @ ..] = tokens {
+        if is_punct(hash, '#') && attr.delimiter() == Delimiter::Bracket {
+            if rest.is_empty() {
+                return error(attr.span(), "expected an entry after the attribute");
+            }
+            return Ok(Entry::Attrs(quote!(#hash #attr), Box::new(entry(rest)?)));
+        }
+    }
+    if let Some(rest) = strip_dot_dot(tokens) {
+        return match rest {
+            [] => error(tokens[1].span(), "expected the type of a map to spread"),
+            [TokenTree::Group(g)] if g.delimiter() == Delimiter::Brace => {
+                Ok(Entry::Spread(object(g)?))
+            }
+            rest => Ok(Entry::Spread(Value::Expr(rest.iter().cloned().collect()))),
+        };
+    }
+
+    let (key, rest) = match tokens {
+        [TokenTree::Literal(key), rest @ ..] => (Value::Expr(quote!(#key)), rest),
+        [TokenTree::Group(key), rest @ ..] if key.delimiter() == Delimiter::Parenthesis => {
+            (Value::Expr(key.stream()), rest)
+        }
+        [tt, ..] => {
+            return error(
+                tt.span(),
+                format!(
+                    "expected a string literal key or its type in parentheses like `(String)`, \
+                     found {}",
+                    describe(tt)
+                ),
+            )
+        }
+        [] => unreachable!("split_commas skips empty entries"),
+    };
+    match rest {
+        [q, colon, rest @ ..] if is_punct(q, '?') && is_punct(colon, ':') => match rest {
+            [] => error(colon.span(), "expected a type"),
+            rest => Ok(Entry::Optional(key, rest.iter().cloned().collect())),
+        },
+        [colon, rest @ ..] if is_punct(colon, ':') && !is_joint(colon, ':') => {
+            Ok(Entry::Pair(key, value(rest, colon.span())?))
+        }
+        [tt, ..] => error(tt.span(), format!("expected `:`, found {}", describe(tt))),
+        [] => error(tokens[0].span(), "expected `:` after the key"),
+    }
+}
+
+/// Split on the commas between elements or entries. A trailing comma is allowed.
+fn split_commas(tokens: &[TokenTree]) -> Result<Vec<&[TokenTree]>> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for i in top_level(tokens) {
+        if is_punct(&tokens[i], ',') {
+            if i == start {
+                return error(tokens[i].span(), "unexpected `,`");
+            }
+            parts.push(&tokens[start..i]);
+            start = i + 1;
+        }
+    }
+    if start < tokens.len() {
+        parts.push(&tokens[start..]);
+    }
+    Ok(parts)
+}
+
+/// The positions of the tokens outside of generic arguments, which have their own `,`.
+fn top_level(tokens: &[TokenTree]) -> impl Iterator<Item = usize> + '_ {
+    let mut depth = 0usize;
+    (0..tokens.len()).filter(move |&i| {
+        if is_punct(&tokens[i], '<') {
+            depth += 1;
+        } else if is_punct(&tokens[i], '>') && !(i > 0 && is_joint(&tokens[i - 1], '-')) {
+            depth = depth.saturating_sub(1);
+            return false;
+        }
+        depth == 0
+    })
+}
+
+impl Value {
+    /// The type of this value, the same as `json!` would produce for it.
+    ///
+    /// `krate` is the path to the `typed_json` crate.
+    pub fn ty(&self, krate: &TokenStream) -> TokenStream {
+        match self {
+            Value::Null => quote!(#krate::__private::Null),
+            Value::Expr(ty) => {
+                let ty = literal(ty).unwrap_or_else(|| ty.clone());
+                quote!(#krate::Expr<#ty>)
+            }
+            Value::Raw(_) => quote!(#krate::Expr<#krate::Raw<'static>>),
+            Value::Array(elements) => {
+                let elements = elements.iter().map(|element| element.ty(krate));
+                let elements = hlist(elements.collect());
+                quote!(#krate::Array<#elements>)
+            }
+            Value::Object(entries) => {
+                let entries = entries.iter().map(|entry| entry.ty(krate));
+                let entries = hlist(entries.collect());
+                quote!(#krate::Map<#entries>)
+            }
+            Value::Format(_)
+            | Value::Comprehension { .. }
+            | Value::If { .. }
+            | Value::Match { .. } => unreachable!("rejected while parsing"),
+        }
+    }
+}
+
+impl Element {
+    fn ty(&self, krate: &TokenStream) -> TokenStream {
+        match self {
+            Element::Value(value) | Element::If(_, value) => {
+                let value = value.ty(krate);
+                quote!(::core::option::Option<#value>)
+            }
+            Element::Spread(iter) => quote!(#krate::__private::Spread<#iter>),
+            Element::Attrs(attrs, element) => cfg_select(krate, attrs, element.ty(krate)),
+        }
+    }
+}
+
+impl Entry {
+    fn ty(&self, krate: &TokenStream) -> TokenStream {
+        match self {
+            Entry::Pair(key, value) => {
+                let key = key.ty(krate);
+                let value = value.ty(krate);
+                quote!(::core::option::Option<#krate::KV<#key, #value>>)
+            }
+            Entry::Optional(key, value) => {
+                let key = key.ty(krate);
+                quote!(::core::option::Option<#krate::KV<#key, #krate::Expr<#value>>>)
+            }
+            Entry::If(_, entry) => entry.ty(krate),
+            Entry::Attrs(attrs, entry) => cfg_select(krate, attrs, entry.ty(krate)),
+            Entry::Spread(Value::Expr(value)) => quote!(#krate::__private::Spread<#value>),
+            Entry::Spread(value) => {
+                let value = value.ty(krate);
+                quote!(#krate::__private::Spread<#value>)
+            }
+            Entry::For { .. } => unreachable!("rejected while parsing"),
+        }
+    }
+}
+
+/// `ty`, or `()` if the `#[cfg(...)]` attributes in `attrs` compile it out.
+fn cfg_select(krate: &TokenStream, attrs: &TokenStream, ty: TokenStream) -> TokenStream {
+    let predicates = cfg_predicates(attrs);
+    if predicates.is_empty() {
+        return ty;
+    }
+    quote! {
+        <#krate::__private::Cfg<{ ::core::cfg!(all(#(#predicates),*)) }>
+            as #krate::__private::Select<#ty>>::Type
+    }
+}
+
+/// The predicates of the `#[cfg(...)]` attributes in `attrs`, which all have to hold for the
+/// entry or element to be compiled in.
+fn cfg_predicates(attrs: &TokenStream) -> Vec<TokenStream> {
+    attrs
+        .clone()
+        .into_iter()
+        .filter_map(|attr| match attr {
+            TokenTree::Group(attr) => match &attr.stream().into_iter().collect::<Vec<_>>()[..] {
+                [TokenTree::Ident(cfg), TokenTree::Group(predicate)] if cfg == "cfg" => {
+                    Some(predicate.stream())
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// The type of a literal, the same as the compiler infers for it on its own.
+fn literal(tokens: &TokenStream) -> Option<TokenStream> {
+    let tokens: Vec<TokenTree> = tokens.clone().into_iter().collect();
+    let lit = match tokens.as_slice() {
+        [TokenTree::Ident(b)] if b == "true" || b == "false" => return Some(quote!(bool)),
+        [TokenTree::Literal(lit)] => lit,
+        [TokenTree::Punct(minus), TokenTree::Literal(lit)] if minus.as_char() == '-' => lit,
+        _ => return None,
+    };
+    let span = lit.span();
+    let lit = lit.to_string();
+    if lit.starts_with('"') || lit.starts_with('r') {
+        return Some(quote!(&'static str));
+    }
+    if lit.starts_with('\'') {
+        return Some(quote!(char));
+    }
+    if lit.starts_with("b'") {
+        return Some(quote!(u8));
+    }
+
+    const INTEGERS: [&str; 12] = [
+        "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+    ];
+    let radix = matches!(lit.get(..2), Some("0x" | "0o" | "0b"));
+    let suffix = INTEGERS
+        .iter()
+        // `f32` is a suffix in decimal, but hex digits in `0x1f32`
+        .chain(if radix { &[][..] } else { &["f32", "f64"][..] })
+        .find(|suffix| lit.ends_with(*suffix));
+    let ty = match suffix {
+        Some(suffix) => suffix,
+        None if !radix && lit.contains(|c| matches!(c, '.' | 'e' | 'E')) => "f64",
+        None => "i32",
+    };
+    let ty = Ident::new(ty, span);
+    Some(quote!(#ty))
+}
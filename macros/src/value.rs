@@ -0,0 +1,271 @@
+//! The typed representation that `json!` produces, and the code that builds it.
+
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+
+use crate::render::{escape, string};
+
+/// A JSON value, where the leaves are Rust expressions.
+pub enum Value {
+    Null,
+    /// Any other leaf, wrapped in `Expr`.
+    Expr(TokenStream),
+    /// `f!(...)`, the arguments to `write!`.
+    Format(TokenStream),
+    /// `raw!(...)`, a compile-time string of JSON.
+    Raw(TokenStream),
+    Array(Vec<Element>),
+    /// `[for pat in iter => value]`
+    Comprehension {
+        pat: TokenStream,
+        iter: TokenStream,
+        value: Box<Value>,
+    },
+    Object(Vec<Entry>),
+    /// `if cond { value } else { value }`, where `otherwise` is another `If` for `else if`.
+    If {
+        cond: TokenStream,
+        then: Box<Value>,
+        otherwise: Box<Value>,
+    },
+    /// `match expr { pat => value, ... }`, where each `pat` includes its guard.
+    Match {
+        expr: TokenStream,
+        arms: Vec<(TokenStream, Value)>,
+    },
+}
+
+/// An element of an array.
+pub enum Element {
+    Value(Value),
+    /// `if cond => value`
+    If(TokenStream, Value),
+    /// `..iter`
+    Spread(TokenStream),
+    /// `#[attr] element`, where a `#[cfg(...)]` can compile the element out.
+    Attrs(TokenStream, Box<Element>),
+}
+
+/// An entry of an object.
+pub enum Entry {
+    /// `key: value`
+    Pair(Value, Value),
+    /// `key?: value`
+    Optional(Value, TokenStream),
+    /// `if cond => entry`
+    If(TokenStream, Box<Entry>),
+    /// `#[attr] entry`, where a `#[cfg(...)]` can compile the entry out.
+    Attrs(TokenStream, Box<Entry>),
+    /// `..value`, either an object literal or an expression that is spread as is.
+    Spread(Value),
+    /// `for pat in iter => key: value`
+    For {
+        pat: TokenStream,
+        iter: TokenStream,
+        key: Value,
+        value: Value,
+    },
+}
+
+impl Value {
+    /// The expression that constructs this value, the same as `json!` would expand to.
+    ///
+    /// `krate` is the path to the `typed_json` crate.
+    pub fn expand(&self, krate: &TokenStream) -> TokenStream {
+        match self {
+            Value::Null => quote!(#krate::__private::Null),
+            Value::Expr(expr) => quote!(#krate::__private::Expr(#expr)),
+            Value::Format(args) => {
+                // hygienic, so it can't shadow a variable used in the arguments
+                let f = Ident::new("f", Span::mixed_site());
+                quote! {
+                    #krate::__private::Expr(#krate::__private::Fmt::new(|#f: &mut ::core::fmt::Formatter<'_>| {
+                        ::core::write!(#f, #args)
+                    }))
+                }
+            }
+            // checked at compile time, the same as `json!`
+            Value::Raw(json) => quote! {
+                #krate::__private::Expr({
+                    const RAW: #krate::Raw<'static> = {
+                        let _: <#krate::__private::Json<{ #krate::__private::is_valid(#json) }>
+                            as #krate::__private::Valid>::Str = #json;
+                        #krate::Raw::new_unchecked(#json)
+                    };
+                    RAW
+                })
+            },
+            Value::Array(elements) => {
+                let elements = elements.iter().map(|element| element.expand(krate));
+                let elements = hlist(elements.collect());
+                quote!(#krate::__private::Array(#elements))
+            }
+            Value::Comprehension { pat, iter, value } => {
+                let value = value.expand(krate);
+                quote!(#krate::Comprehension::new(#iter, |#pat| #value))
+            }
+            Value::Object(entries) => {
+                let entries = entries.iter().map(|entry| entry.expand(krate));
+                let entries = hlist(entries.collect());
+                quote!(#krate::__private::Map(#entries))
+            }
+            Value::If {
+                cond,
+                then,
+                otherwise,
+            } => {
+                let then = then.expand(krate);
+                let otherwise = otherwise.expand(krate);
+                quote! {
+                    if #cond {
+                        #krate::Either::Left(#then)
+                    } else {
+                        #krate::Either::Right(#otherwise)
+                    }
+                }
+            }
+            Value::Match { expr, arms } => {
+                // the same as `else if`: `Left` for this arm, in a `Right` for each arm before it
+                let last = arms.len() - 1;
+                let arms = arms.iter().enumerate().map(|(i, (pat, value))| {
+                    let mut value = value.expand(krate);
+                    if i < last {
+                        value = quote!(#krate::Either::Left(#value));
+                    }
+                    for _ in 0..i {
+                        value = quote!(#krate::Either::Right(#value));
+                    }
+                    quote!(#pat => #value,)
+                });
+                quote!(match #expr { #(#arms)* })
+            }
+        }
+    }
+}
+
+impl Element {
+    fn expand(&self, krate: &TokenStream) -> TokenStream {
+        match self {
+            Element::Value(value) => {
+                let value = value.expand(krate);
+                quote!(::core::option::Option::Some(#value))
+            }
+            Element::If(cond, value) => {
+                let value = value.expand(krate);
+                quote! {
+                    if #cond {
+                        ::core::option::Option::Some(#value)
+                    } else {
+                        ::core::option::Option::None
+                    }
+                }
+            }
+            Element::Spread(iter) => quote!(#krate::__private::Spread(#iter)),
+            Element::Attrs(attrs, element) => {
+                let element = element.expand(krate);
+                quote! {{
+                    #[allow(unused_variables, clippy::let_unit_value)]
+                    let element = ();
+                    #attrs
+                    let element = #element;
+                    element
+                }}
+            }
+        }
+    }
+}
+
+impl Entry {
+    fn expand(&self, krate: &TokenStream) -> TokenStream {
+        match self {
+            Entry::Pair(key, value) => {
+                // a string literal key is also written out as `"key":`, for `Display`
+                let rendered = match key {
+                    Value::Expr(key) => string(key).map(|key| format!("{}:", escape(&key))),
+                    _ => None,
+                };
+                let key = key.expand(krate);
+                let value = value.expand(krate);
+                match rendered {
+                    Some(rendered) => quote! {
+                        ::core::option::Option::Some(#krate::__private::KV::Rendered(
+                            {
+                                const KEY: #krate::__private::Rendered =
+                                    #krate::__private::Rendered::new(#rendered);
+                                KEY
+                            },
+                            #key,
+                            #value,
+                        ))
+                    },
+                    None => quote! {
+                        ::core::option::Option::Some(#krate::__private::KV::Pair(#key, #value))
+                    },
+                }
+            }
+            Entry::Optional(key, value) => {
+                let key = key.expand(krate);
+                quote!(#krate::__private::KV::optional(#key, #value))
+            }
+            Entry::If(cond, entry) => {
+                let entry = entry.expand(krate);
+                quote! {
+                    if #cond {
+                        #entry
+                    } else {
+                        ::core::option::Option::None
+                    }
+                }
+            }
+            // a `()` is left in place of an entry that is compiled out, which adds nothing
+            Entry::Attrs(attrs, entry) => {
+                let entry = entry.expand(krate);
+                quote! {{
+                    #[allow(unused_variables, clippy::let_unit_value)]
+                    let entry = ();
+                    #attrs
+                    let entry = #entry;
+                    entry
+                }}
+            }
+            Entry::Spread(Value::Expr(value)) => quote!(#krate::__private::Spread(#value)),
+            Entry::Spread(value) => {
+                let value = value.expand(krate);
+                quote!(#krate::__private::Spread(#value))
+            }
+            Entry::For {
+                pat,
+                iter,
+                key,
+                value,
+            } => {
+                let key = key.expand(krate);
+                let value = value.expand(krate);
+                quote!(#krate::__private::Entries::new(#iter, |#pat| (#key, #value)))
+            }
+        }
+    }
+}
+
+/// Nest the items into balanced pairs, the same shape as the `hlist!` macro.
+///
+/// `[a, b, c, d, e]` becomes `(a, ((b, c), (d, e)))`.
+pub fn hlist(mut items: Vec<TokenStream>) -> TokenStream {
+    loop {
+        match items.len() {
+            0 => return quote!(()),
+            1 => return items.pop().unwrap(),
+            len => {
+                let mut iter = items.into_iter();
+                let mut pairs = Vec::with_capacity(len / 2 + 1);
+                if len % 2 == 1 {
+                    pairs.extend(iter.next());
+                }
+                while let (Some(a), Some(b)) = (iter.next(), iter.next()) {
+                    pairs.push(quote!((#a, #b)));
+                }
+                items = pairs;
+            }
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use typed_json::json;
+
+/// The `macro_rules!` backend of `json!`, which the `proc-macro` feature replaces.
+macro_rules! json_rules {
+    ($($json:tt)+) => {
+        typed_json::json_internal!($($json)+)
+    };
+}
+
+/// Asserts at compile time that both values have the same type.
+fn same_type<T>(_: &T, _: &T) {}
+
+fn to_string(value: &impl serde::Serialize) -> String {
+    serde_json::to_string(value).unwrap()
+}
+
+#[test]
+fn same_types() {
+    let value = 1;
+    let extra = HashMap::<String, i32>::from_iter([("extra".to_owned(), 2)]);
+    let missing: Option<i32> = None;
+    let user_id = 7;
+    let enabled = true;
+
+    macro_rules! both {
+        ($($json:tt)+) => {{
+            let a = json!($($json)+);
+            let b = json_rules!($($json)+);
+            same_type(&a, &b);
+            assert_eq!(to_string(&a), to_string(&b));
+            assert_eq!(a.to_string(), b.to_string());
+            to_string(&a)
+        }};
+    }
+
+    assert_eq!(both!(null), "null");
+    assert_eq!(both!(value), "1");
+    assert_eq!(both!([]), "[]");
+    assert_eq!(both!({}), "{}");
+    assert_eq!(
+        both!({
+            "foo": value,
+            "bar": [value, "str", null, true, false, 1.5, -2],
+            "baz": { "empty": {}, "none": [] },
+            "sum": value + 1,
+            "map": HashMap::<String, i32>::new(),
+            "raw": raw!("[1, 2]"),
+        }),
+        r#"{"foo":1,"bar":[1,"str",null,true,false,1.5,-2],"baz":{"empty":{},"none":[]},"sum":2,"map":{},"raw":[1, 2]}"#
+    );
+    assert_eq!(
+        both!({
+            ..{ "a": 1 },
+            ..&extra,
+            "missing"?: missing,
+            if enabled => "enabled": true,
+            if !enabled => "disabled": true,
+            (if enabled { "on" } else { "off" }): value,
+            if enabled { "b" } else { "c" }: value,
+        }),
+        r#"{"a":1,"extra":2,"enabled":true,"on":1,"b":1}"#
+    );
+    assert_eq!(
+        both!([..[1, 2], if enabled => 3, if !enabled => 4, if enabled { 5 } else { 6 }]),
+        "[1,2,3,5]"
+    );
+    assert_eq!(
+        both!({
+            "if": if enabled { { "value": value } } else if !enabled { null } else { [user_id] },
+            "match": match value {
+                0 => value,
+                n if n > 0 => { "positive": n },
+                _ => [value],
+            },
+        }),
+        r#"{"if":{"value":1},"match":{"positive":1}}"#
+    );
+    assert_eq!(
+        both!({
+            #![rename_all = "camelCase"]
+            #[cfg(debug_assertions)]
+            user_id,
+            #[cfg(not(debug_assertions))]
+            "user_id": missing,
+            #[cfg(any())]
+            ..&extra,
+            #[cfg(all())] if enabled => "value": value,
+        }),
+        if cfg!(debug_assertions) {
+            r#"{"userId":7,"value":1}"#
+        } else {
+            r#"{"user_id":null,"value":1}"#
+        }
+    );
+    assert_eq!(
+        both!([
+            1,
+            #[cfg(debug_assertions)]
+            user_id,
+            #[cfg(not(debug_assertions))]
+            missing,
+            #[cfg(any())] ..&extra,
+            #[cfg(all())] #[allow(unused_parens)]
+            if enabled => (value),
+            #[cfg(all())]
+            [null, { "id": user_id }]
+        ]),
+        if cfg!(debug_assertions) {
+            r#"[1,7,1,[null,{"id":7}]]"#
+        } else {
+            r#"[1,null,1,[null,{"id":7}]]"#
+        }
+    );
+    assert_eq!(
+        both!({ #![rename_all = "camelCase"] user_id, if enabled => enabled }),
+        r#"{"userId":7,"enabled":true}"#
+    );
+}
+
+#[test]
+fn sort_keys() {
+    let id = 7;
+    let debug = false;
+
+    // the same as the keys written in order, so the types match too
+    let sorted = json!({
+        #![rename_all = "UPPERCASE"]
+        #![sort_keys]
+        "type": "push",
+        id,
+        if debug => "\u{1f600}": [1, id],
+        "\u{fb33}": null,
+        #[cfg(any())]
+        "a": unknown,
+        "tags"?: Some(["b", "a"]),
+        2: { #![sort_keys] "y": id, "x": 1 },
+    });
+    let written = json!({
+        2: { "x": 1, "y": id },
+        "ID": id,
+        #[cfg(any())]
+        "a": unknown,
+        "tags"?: Some(["b", "a"]),
+        "type": "push",
+        if debug => "\u{1f600}": [1, id],
+        "\u{fb33}": null,
+    });
+    same_type(&sorted, &written);
+    assert_eq!(to_string(&sorted), to_string(&written));
+    assert_eq!(
+        typed_json::to_canonical_string(&sorted).unwrap(),
+        to_string(&sorted)
+    );
+}
+
+#[test]
+fn same_output() {
+    let name = "John";
+    let f = "shadowed";
+    let items = [1, 2];
+
+    assert_eq!(
+        to_string(&json_rules!({
+            f!("{}_id", name): f!("{} {}", name, f),
+            "greeting": format_args!("hello {}", name),
+            "items": [for i in items => { "id": i }],
+            "by_id": { for i in items => i: i * 10 },
+        })),
+        to_string(&json!({
+            f!("{}_id", name): f!("{} {}", name, f),
+            "greeting": format_args!("hello {}", name),
+            "items": [for i in items => { "id": i }],
+            "by_id": { for i in items => i: i * 10 },
+        })),
+    );
+}
+
+#[test]
+fn display() {
+    let value = 1;
+
+    macro_rules! check {
+        ($($json:tt)+) => {{
+            let a = json!($($json)+);
+            let b = json_rules!($($json)+);
+            assert_eq!(to_string(&a), to_string(&b));
+            assert_eq!(a.to_string(), to_string(&b));
+            assert_eq!(b.to_string(), to_string(&b));
+            assert_eq!(
+                serde_json::to_string_pretty(&a).unwrap(),
+                serde_json::to_string_pretty(&b).unwrap(),
+            );
+            assert_eq!(format!("{:#}", a), serde_json::to_string_pretty(&b).unwrap());
+            assert_eq!(
+                to_string(&typed_json::Strict(&a)),
+                to_string(&typed_json::Strict(&b)),
+            );
+        }};
+    }
+
+    check!("quote \" backslash \\ newline \n tab \t nul \0 bell \x07 del \x7f slash / é 🦀");
+    check!(r#"raw "string""#);
+    check!(['a', '"', '\'', '\n', '\u{1}', 'é']);
+    check!([
+        0,
+        -0,
+        -1,
+        1_000,
+        0xff,
+        -0o17,
+        0b101,
+        1u8,
+        -128i8,
+        340282366920938463463374607431768211455u128
+    ]);
+    check!([
+        i64::MAX,
+        -170141183460469231731687303715884105728i128,
+        1.5,
+        -2.5e3,
+        1f32
+    ]);
+    check!([true, false, null, [], {}, [[]], [{}]]);
+    check!({ "a": { "b": ["c", { "d": null }] }, "e\n": [1, 2] });
+    check!({
+        "plain": 1,
+        "quote \" backslash \\": 2,
+        r#"raw "key""#: 3,
+        r"raw": 4,
+        "tab\t nul \0 del \x7f": 5,
+        "é 🦀 \u{2028}": 6,
+        'c': 7,
+        8: [{ "nested": { "deeper": [{ "deepest": value }] } }],
+        value: "interpolated",
+    });
+    check!([1, 2, value, "a", "b", value, [3, "c"]]);
+    check!({ "a": [value, "x"], "b": { "c": value, "d": "y" } });
+    check!({ "raw": raw!("[1, 2]"), "key": raw!(r#"{"a": "b"}"#) });
+    check!({ "bytes": b"ab", "nested": { "bytes": [b"a"] } });
+    check!([for i in 0..2 => { "i": i, "const": ["a", "b"] }]);
+    check!({ ..{ "a": "b", "c": ["d"] }, if value > 0 => "e": { "f": "g" } });
+
+    // both backends write a plain literal key out ahead of time
+    for value in [json!({ "a": 1 }), json_rules!({ "a": 1 })] {
+        assert!(matches!(
+            value.0,
+            Some(typed_json::KV::Rendered(key, ..)) if key.get() == Some("\"a\":")
+        ));
+    }
+
+    // deserializing reads the keys, not the rendered JSON
+    assert_eq!(
+        serde_json::Value::deserialize(
+            json!({ "a": ["b", 1, value, "c", [null]], "d": { "e": "f" } })
+        )
+        .unwrap(),
+        serde_json::json!({ "a": ["b", 1, 1, "c", [null]], "d": { "e": "f" } }),
+    );
+}
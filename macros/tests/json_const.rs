@@ -0,0 +1,81 @@
+use typed_json::{json, json_const, ConstJson};
+
+/// Asserts that both forms are exactly what `serde_json` writes for the same `json!` literal.
+macro_rules! check {
+    ($($json:tt)+) => {{
+        const VALUE: ConstJson = json_const!($($json)+);
+        let expected = json!($($json)+);
+        assert_eq!(VALUE.compact(), serde_json::to_string(&expected).unwrap());
+        assert_eq!(VALUE.pretty(), serde_json::to_string_pretty(&expected).unwrap());
+    }};
+}
+
+#[test]
+fn same_as_serde_json() {
+    check!(null);
+    check!("quote \" backslash \\ newline \n tab \t nul \0 bell \x07 del \x7f slash / é 🦀");
+    check!(r#"raw "string""#);
+    check!(['a', '"', '\'', '\n', '\u{1}', 'é']);
+    check!([
+        0,
+        -0,
+        -1,
+        1_000,
+        0xff,
+        -0o17,
+        0b101,
+        1u8,
+        -128i8,
+        340282366920938463463374607431768211455u128
+    ]);
+    check!([
+        1.5,
+        -2.5e3,
+        1e-5,
+        0.1,
+        -0.0,
+        1f32,
+        0.3f32,
+        5e-324,
+        123456789.12345679
+    ]);
+    check!([true, false, null, [], {}, [[]], [{}], [1, [2, [3, []]]]]);
+    check!({ "a": { "b": ["c", { "d": null }] }, "e\n": [1, 2], "f": {} });
+    check!({ "raw": raw!("[1,2]"), "key": raw!(r#"{"a":"b"}"#) });
+}
+
+#[test]
+fn floats() {
+    // `serde_json` versions differ on how to write exponents, but not on the number
+    const VALUE: ConstJson = json_const!([1e16, -1.0e300, 2.5e-10]);
+    let expected = serde_json::to_value(json!([1e16, -1.0e300, 2.5e-10])).unwrap();
+    for json in [VALUE.compact(), VALUE.pretty()] {
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(json).unwrap(),
+            expected
+        );
+    }
+}
+
+#[test]
+fn serialize() {
+    const HEALTH: ConstJson = json_const!({ "status": "ok", "checks": ["db", "cache"] });
+    const BODY: &str = HEALTH.compact();
+
+    assert_eq!(BODY, r#"{"status":"ok","checks":["db","cache"]}"#);
+    assert_eq!(HEALTH.to_string(), BODY);
+    assert_eq!(format!("{:#}", HEALTH), HEALTH.pretty());
+
+    // the same indent and depth options as a `json!` value
+    let expected = json!({ "status": "ok", "checks": ["db", "cache"] });
+    assert_eq!(format!("{:#4}", HEALTH), format!("{:#4}", expected));
+    assert_eq!(format!("{:\t<#}", HEALTH), format!("{:\t<#}", expected));
+    assert_eq!(format!("{:#.1}", HEALTH), format!("{:#.1}", expected));
+    assert_eq!(format!("{:5}", HEALTH), BODY);
+
+    let value = json!([HEALTH, { "nested": HEALTH }]);
+    assert_eq!(
+        serde_json::to_string(&value).unwrap(),
+        format!(r#"[{},{{"nested":{}}}]"#, BODY, BODY)
+    );
+}
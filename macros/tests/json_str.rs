@@ -0,0 +1,64 @@
+use typed_json::{include_json, json, json_str};
+
+/// Asserts at compile time that both values have the same type.
+fn same_type<T>(_: &T, _: &T) {}
+
+#[test]
+fn same_as_json() {
+    let value = 1;
+    let a = json!({
+        "foo": value,
+        "bar": [value, "str", null, true, false, 1.5, -2],
+        "baz": { "empty": {}, "none": [] },
+    });
+    let b = json_str!(
+        r#"{
+            "foo": {{value}},
+            "bar": [{{value}}, "str", null, true, false, 1.5, -2],
+            "baz": { "empty": {}, "none": [] }
+        }"#
+    );
+    same_type(&a, &b);
+    assert_eq!(
+        serde_json::to_string(&a).unwrap(),
+        serde_json::to_string(&b).unwrap()
+    );
+}
+
+#[test]
+fn placeholders() {
+    let key = "dynamic";
+    let values = vec![1, 2];
+    let value =
+        json_str!(r#"{ {{key}}: {{ &values }}, "nested": {{ json!({ "a": values[0] }) }} }"#);
+    assert_eq!(
+        serde_json::to_string(&value).unwrap(),
+        r#"{"dynamic":[1,2],"nested":{"a":1}}"#
+    );
+}
+
+#[test]
+fn strings_and_numbers() {
+    let value =
+        json_str!(r#"["tab\t", "é😀", "\/", 3000000000, -3000000000, 18446744073709551615, 1e3]"#);
+    assert_eq!(
+        serde_json::to_string(&value).unwrap(),
+        r#"["tab\t","é😀","/",3000000000,-3000000000,18446744073709551615,1000.0]"#
+    );
+}
+
+#[test]
+fn include() {
+    let name = "my-service";
+    let port = 80;
+    let value = include_json!("tests/fixtures/service.json");
+    assert_eq!(
+        serde_json::to_string(&value).unwrap(),
+        concat!(
+            r#"{"apiVersion":"v1","kind":"Service","#,
+            r#""metadata":{"name":"my-service","labels":{"app":"my-service","tier":"backend"}},"#,
+            r#""spec":{"ports":[{"port":80,"targetPort":9376,"protocol":"TCP"}],"clusterIP":null,"#,
+            r#""publishNotReadyAddresses":false,"sessionAffinityConfig":{"clientIP":{"timeoutSeconds":10800}}}}"#,
+        )
+    );
+}
@@ -0,0 +1,48 @@
+use typed_json::{json, json_type};
+
+struct Page<'a> {
+    body: json_type!({
+        "kind": "page",
+        "items": ["first", "second", &'a str, 1, 2, { "nested": [true, null] }],
+        "links": { "self": &'a str, "next"?: &'a str },
+        "meta": { "version": 2, "tags": ["a", "b"] },
+    }),
+}
+
+#[test]
+fn constants() {
+    let (name, url) = ("third", "/page/1");
+    let page = Page {
+        body: json!({
+            "kind": "page",
+            "items": ["first", "second", name, 1, 2, { "nested": [true, null] }],
+            "links": { "self": url, "next"?: None::<&str> },
+            "meta": { "version": 2, "tags": ["a", "b"] },
+        }),
+    };
+    assert_eq!(
+        serde_json::to_string(&page.body).unwrap(),
+        r#"{"kind":"page","items":["first","second","third",1,2,{"nested":[true,null]}],"links":{"self":"/page/1"},"meta":{"version":2,"tags":["a","b"]}}"#
+    );
+}
+
+#[test]
+fn literals() {
+    static VALUES: json_type!([-1, 2u8, 0x1f32, 1.5, 1e3f32, 'c', b'b', r"raw", false]) =
+        json!([-1, 2u8, 0x1f32, 1.5, 1e3f32, 'c', b'b', r"raw", false]);
+    assert_eq!(
+        serde_json::to_string(&VALUES).unwrap(),
+        r#"[-1,2,7986,1.5,1000.0,"c",98,"raw",false]"#
+    );
+}
+
+#[test]
+fn sort_keys() {
+    let (b, c) = (1u8, None);
+    let sorted: json_type!({ #![sort_keys] "b": u8, "a": [true, false], "c"?: &str }) =
+        json!({ #![sort_keys] "b": b, "c"?: c, "a": [true, false] });
+    assert_eq!(
+        serde_json::to_string(&sorted).unwrap(),
+        r#"{"a":[true,false],"b":1}"#
+    );
+}
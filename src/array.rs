@@ -1,4 +1,8 @@
-use crate::DeShared;
+use core::fmt;
+
+use crate::ser::{Compact, Compound, Error};
+use crate::spread::Spread;
+use crate::{exact_len, DeShared, Expr};
 
 impl<'de, T> ItemDe<'de> for Option<T>
 where
@@ -20,7 +24,24 @@ impl<T> ItemSer for Option<T>
 where
     T: serde::ser::Serialize,
 {
-    fn serialize<S>(&self, seq: &mut S) -> Result<(), S::Error>
+    #[inline]
+    fn elements<W: WithElements>(&self, with: W) -> W::Output {
+        with.with(self.as_ref())
+    }
+}
+
+impl<T> Elements for Option<&T>
+where
+    T: serde::ser::Serialize,
+{
+    #[inline]
+    fn len(&self) -> Option<usize> {
+        // conditional elements left out of the array are `None`
+        Some(self.is_some() as usize)
+    }
+
+    #[inline]
+    fn serialize<S>(self, seq: &mut S) -> Result<(), S::Error>
     where
         S: serde::ser::SerializeSeq,
     {
@@ -29,9 +50,67 @@ where
         }
         Ok(())
     }
+}
+
+impl<T: fmt::Display> ItemFmt for Option<T> {
+    #[inline]
+    fn fmt(&self, seq: &mut Compound<'_, &mut fmt::Formatter<'_>, Compact>) -> Result<(), Error> {
+        if let Some(x) = self {
+            seq.element_with(|f| x.fmt(f))?;
+        }
+        Ok(())
+    }
+}
 
-    fn size(&self) -> usize {
-        1
+impl<I> ItemSer for Spread<I>
+where
+    I: IntoIterator + Clone,
+    I::Item: serde::ser::Serialize,
+{
+    #[inline]
+    fn elements<W: WithElements>(&self, with: W) -> W::Output {
+        // cloned once, so the length comes from the same iterator that is walked. A collection
+        // spread by value is cloned whole, which is why the docs spread `..&vec`
+        with.with(Walk(self.0.clone().into_iter()))
+    }
+}
+
+/// The items of a spread, walked once.
+struct Walk<I>(I);
+
+impl<I> Elements for Walk<I>
+where
+    I: Iterator,
+    I::Item: serde::ser::Serialize,
+{
+    #[inline]
+    fn len(&self) -> Option<usize> {
+        exact_len(&self.0)
+    }
+
+    #[inline]
+    fn serialize<S>(self, seq: &mut S) -> Result<(), S::Error>
+    where
+        S: serde::ser::SerializeSeq,
+    {
+        for item in self.0 {
+            seq.serialize_element(&item)?;
+        }
+        Ok(())
+    }
+}
+
+impl<I> ItemFmt for Spread<I>
+where
+    I: IntoIterator + Clone,
+    I::Item: serde::ser::Serialize,
+{
+    #[inline]
+    fn fmt(&self, seq: &mut Compound<'_, &mut fmt::Formatter<'_>, Compact>) -> Result<(), Error> {
+        for item in self.0.clone() {
+            serde::ser::SerializeSeq::serialize_element(seq, &item)?;
+        }
+        Ok(())
     }
 }
 
@@ -58,18 +137,60 @@ where
     U: ItemSer,
 {
     #[inline]
-    fn serialize<S>(&self, seq: &mut S) -> Result<(), S::Error>
+    fn elements<W: WithElements>(&self, with: W) -> W::Output {
+        self.0.elements(Then(&self.1, with))
+    }
+}
+
+/// Gets the elements of the second item of a pair, once those of the first are ready.
+struct Then<'a, U, W>(&'a U, W);
+
+impl<U: ItemSer, W: WithElements> WithElements for Then<'_, U, W> {
+    type Output = W::Output;
+
+    #[inline]
+    fn with<E: Elements>(self, first: E) -> W::Output {
+        self.0.elements(Both(first, self.1))
+    }
+}
+
+/// Passes on the elements of both items of a pair.
+struct Both<E, W>(E, W);
+
+impl<E: Elements, W: WithElements> WithElements for Both<E, W> {
+    type Output = W::Output;
+
+    #[inline]
+    fn with<F: Elements>(self, second: F) -> W::Output {
+        self.1.with((self.0, second))
+    }
+}
+
+impl<E: Elements, F: Elements> Elements for (E, F) {
+    #[inline]
+    fn len(&self) -> Option<usize> {
+        Some(self.0.len()? + self.1.len()?)
+    }
+
+    #[inline]
+    fn serialize<S>(self, seq: &mut S) -> Result<(), S::Error>
     where
         S: serde::ser::SerializeSeq,
     {
         self.0.serialize(seq)?;
-        self.1.serialize(seq)?;
-        Ok(())
+        self.1.serialize(seq)
     }
+}
 
+impl<T, U> ItemFmt for (T, U)
+where
+    T: ItemFmt,
+    U: ItemFmt,
+{
     #[inline]
-    fn size(&self) -> usize {
-        self.0.size() + self.1.size()
+    fn fmt(&self, seq: &mut Compound<'_, &mut fmt::Formatter<'_>, Compact>) -> Result<(), Error> {
+        self.0.fmt(seq)?;
+        self.1.fmt(seq)
     }
 }
 
@@ -83,16 +204,30 @@ impl<'de> ItemDe<'de> for () {
 }
 impl ItemSer for () {
     #[inline]
-    fn serialize<S>(&self, _seq: &mut S) -> Result<(), S::Error>
+    fn elements<W: WithElements>(&self, with: W) -> W::Output {
+        with.with(())
+    }
+}
+
+impl Elements for () {
+    #[inline]
+    fn len(&self) -> Option<usize> {
+        Some(0)
+    }
+
+    #[inline]
+    fn serialize<S>(self, _seq: &mut S) -> Result<(), S::Error>
     where
         S: serde::ser::SerializeSeq,
     {
         Ok(())
     }
+}
 
+impl ItemFmt for () {
     #[inline]
-    fn size(&self) -> usize {
-        0
+    fn fmt(&self, _seq: &mut Compound<'_, &mut fmt::Formatter<'_>, Compact>) -> Result<(), Error> {
+        Ok(())
     }
 }
 
@@ -102,17 +237,96 @@ trait ItemDe<'de>: DeShared {
         V: serde::de::DeserializeSeed<'de>;
 }
 
+/// The elements of an [`Array`], which are handed to a [`WithElements`] rather than returned, as
+/// they borrow from the array.
 pub trait ItemSer {
-    fn serialize<S>(&self, seq: &mut S) -> Result<(), S::Error>
+    fn elements<W: WithElements>(&self, with: W) -> W::Output;
+}
+
+/// What to do with the elements of an [`Array`].
+pub trait WithElements {
+    type Output;
+    fn with<E: Elements>(self, elements: E) -> Self::Output;
+}
+
+/// The elements of an [`Array`], ready to be serialized. Each spread is already turned into its
+/// iterator, so that it is only cloned once.
+pub trait Elements {
+    fn len(&self) -> Option<usize>;
+    fn serialize<S>(self, seq: &mut S) -> Result<(), S::Error>
     where
         S: serde::ser::SerializeSeq;
-    fn size(&self) -> usize;
 }
 
+/// The elements of an [`Array`] for its compact `Display`, where each element is written by its
+/// own `Display`.
+pub trait ItemFmt {
+    fn fmt(&self, seq: &mut Compound<'_, &mut fmt::Formatter<'_>, Compact>) -> Result<(), Error>;
+}
+
+/// A JSON array, as built by [`json!`](crate::json).
+///
+/// Each element is an `Option`, which is `None` for a conditional element that is left out.
+/// A spread `..iter` is an item of its own, which writes all the items of the iterator. The
+/// elements are nested into balanced pairs, so that `[a, b, c, d, e]` holds
+/// `(a, ((b, c), (d, e)))`, and an empty array holds `()`.
+///
+/// ```
+/// # use typed_json::{json, Array, Expr};
+/// #
+/// let (admin, name) = (true, "John Doe");
+/// let value: Array<(Option<Expr<i32>>, Option<Expr<&str>>)> = json!([1, if admin => name]);
+///
+/// assert_eq!(serde_json::to_string(&value).unwrap(), r#"[1,"John Doe"]"#);
+/// ```
 #[derive(Copy, Clone)]
-#[doc(hidden)]
 pub struct Array<T>(pub T);
 
+impl Array<()> {
+    /// An empty array, to build up with [`push`](Array::push).
+    ///
+    /// Each call adds a new element to the type, nested to the left like the entries of
+    /// [`Map::new`](crate::Map::new), so it is a different type to the balanced
+    /// [`json!`](crate::json) array with the same elements. The JSON is the same.
+    ///
+    /// ```
+    /// # use typed_json::{json, Array};
+    /// #
+    /// let value = Array::new().push(1).push("two").push(None::<u8>);
+    ///
+    /// assert_eq!(
+    ///     serde_json::to_string(&value).unwrap(),
+    ///     serde_json::to_string(&json!([1, "two", null])).unwrap(),
+    /// );
+    /// ```
+    #[inline]
+    pub const fn new() -> Self {
+        Array(())
+    }
+}
+
+impl Default for Array<()> {
+    #[inline]
+    fn default() -> Self {
+        Array::new()
+    }
+}
+
+impl<T> Array<T> {
+    /// Add `value` to the end, the same as an element in [`json!`](crate::json).
+    #[inline]
+    pub fn push<V>(self, value: V) -> Array<(T, Option<Expr<V>>)> {
+        Array((self.0, Some(Expr(value))))
+    }
+
+    /// Add `value` to the end if `cond` is true, the same as `if cond => value` in
+    /// [`json!`](crate::json).
+    #[inline]
+    pub fn push_if<V>(self, cond: bool, value: V) -> Array<(T, Option<Expr<V>>)> {
+        Array((self.0, if cond { Some(Expr(value)) } else { None }))
+    }
+}
+
 struct ListState<T>(T);
 
 impl<'de, T: ItemDe<'de>> crate::Deserializer<'de> for Array<T> {
@@ -151,8 +365,78 @@ impl<T: ItemSer> serde::ser::Serialize for Array<T> {
     where
         S: serde::Serializer,
     {
-        let mut seq = serializer.serialize_seq(Some(self.0.size()))?;
-        self.0.serialize(&mut seq)?;
+        self.0.elements(Start(serializer))
+    }
+}
+
+/// Serializes the elements of an [`Array`], once their number is known.
+struct Start<S>(S);
+
+impl<S: serde::Serializer> WithElements for Start<S> {
+    type Output = Result<S::Ok, S::Error>;
+
+    #[inline]
+    fn with<E: Elements>(self, elements: E) -> Self::Output {
+        let mut seq = self.0.serialize_seq(elements.len())?;
+        elements.serialize(&mut seq)?;
+        serde::ser::SerializeSeq::end(seq)
+    }
+}
+
+/// A JSON array built lazily from an iterator, created by
+/// `[for pat in iter => value]` inside [`json!`](crate::json).
+///
+/// Each item of the iterator is mapped into a JSON value while serializing,
+/// without collecting them into a `Vec` first.
+///
+/// ```
+/// # use typed_json::json;
+/// #
+/// let users = [("alice", 1), ("bob", 2)];
+///
+/// let value = json!([for (name, id) in users => {
+///     "id": id,
+///     "name": name,
+/// }]);
+///
+/// assert_eq!(
+///     serde_json::to_string(&value).unwrap(),
+///     r#"[{"id":1,"name":"alice"},{"id":2,"name":"bob"}]"#,
+/// );
+/// ```
+#[derive(Copy, Clone)]
+pub struct Comprehension<I, F> {
+    pub(crate) iter: I,
+    pub(crate) f: F,
+}
+
+impl<I, F> Comprehension<I, F> {
+    #[doc(hidden)]
+    #[inline]
+    pub fn new<T>(iter: I, f: F) -> Self
+    where
+        I: IntoIterator + Clone,
+        F: Fn(I::Item) -> T,
+    {
+        Comprehension { iter, f }
+    }
+}
+
+impl<I, F, T> serde::ser::Serialize for Comprehension<I, F>
+where
+    I: IntoIterator + Clone,
+    F: Fn(I::Item) -> T,
+    T: serde::ser::Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let iter = self.iter.clone().into_iter();
+        let mut seq = serializer.serialize_seq(exact_len(&iter))?;
+        for item in iter {
+            serde::ser::SerializeSeq::serialize_element(&mut seq, &(self.f)(item))?;
+        }
         serde::ser::SerializeSeq::end(seq)
     }
 }
@@ -0,0 +1,715 @@
+//! Canonical JSON, as specified by [RFC 8785](https://www.rfc-editor.org/rfc/rfc8785) (JCS).
+//!
+//! The same data always serializes to the same bytes, so the output can be signed or hashed.
+//! Objects are buffered so that their entries can be sorted, which is why this needs `std`.
+
+use std::fmt::Write;
+
+use serde::ser::{self, Error as _, Serialize};
+
+use crate::raw::TOKEN;
+use crate::ser::{write_str, Error, ErrorKind};
+
+/// Serialize `value` as canonical JSON, as specified by
+/// [RFC 8785](https://www.rfc-editor.org/rfc/rfc8785).
+///
+/// - The entries of every object are sorted by their keys, compared as UTF-16 code units.
+/// - Numbers are written the same as ECMAScript's `Number.prototype.toString`. Like in
+///   JavaScript, every number is a double, so integers beyond ±2<sup>53</sup> are rounded.
+/// - Strings only escape `"`, `\` and control characters.
+/// - There is no whitespace.
+///
+/// NaN and infinities can't be represented, and are an error instead of `null`. So is an object
+/// with the same key twice, as RFC 8785 only takes I-JSON, where keys are unique. A
+/// [`Raw`](crate::Raw) fragment is parsed, so that it is canonical too.
+///
+/// ```
+/// # use typed_json::json;
+/// #
+/// let value = json!({ "b": [1.0, 1e21, 0.000001], "a": { "€": "euro", "z": null } });
+///
+/// assert_eq!(
+///     typed_json::to_canonical_string(&value).unwrap(),
+///     r#"{"a":{"z":null,"€":"euro"},"b":[1,1e+21,0.000001]}"#,
+/// );
+/// ```
+///
+/// Sorting allocates for every object. An object whose keys are all literals can be sorted at
+/// compile time instead with `#![sort_keys]`, see [`json!`](crate::json).
+pub fn to_canonical_string<T>(value: &T) -> Result<String, Error>
+where
+    T: ?Sized + Serialize,
+{
+    let mut out = String::new();
+    value.serialize(Canonical(&mut out))?;
+    Ok(out)
+}
+
+/// Writes canonical JSON onto the end of a `String`.
+struct Canonical<'a>(&'a mut String);
+
+/// Write an integer. It stays exact if a double can hold it, and is rounded otherwise.
+fn write_int(out: &mut String, value: i128) -> Result<(), Error> {
+    if value.unsigned_abs() <= 1 << 53 {
+        write!(out, "{}", value)?;
+        Ok(())
+    } else {
+        write_number(out, value as f64)
+    }
+}
+
+/// Write a double the same way as ECMAScript's `Number.prototype.toString`.
+fn write_number(out: &mut String, value: f64) -> Result<(), Error> {
+    if !value.is_finite() {
+        return Err(Error(ErrorKind::NumberMustBeFinite));
+    }
+    if value == 0.0 {
+        // including -0
+        out.push('0');
+        return Ok(());
+    }
+    if value < 0.0 {
+        out.push('-');
+    }
+
+    // `ryu` finds the shortest digits that round trip, but lays them out differently, so take
+    // them apart into the digits and `n`, where the value is `0.digits * 10^n`.
+    let mut buffer = ryu::Buffer::new();
+    let written = buffer.format_finite(value.abs());
+    let (mantissa, exp) = match written.split_once('e') {
+        Some((mantissa, exp)) => (mantissa, exp.parse::<i32>().unwrap_or_default()),
+        None => (written, 0),
+    };
+    let point = mantissa.find('.').unwrap_or(mantissa.len());
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let leading = digits.len() - digits.trim_start_matches('0').len();
+    let digits = digits.trim_matches('0');
+    let k = digits.len() as i32;
+    let n = point as i32 - leading as i32 + exp;
+
+    if k <= n && n <= 21 {
+        // 1e21 is the first integer that is written with an exponent
+        out.push_str(digits);
+        out.extend((k..n).map(|_| '0'));
+    } else if 0 < n && n <= 21 {
+        out.push_str(&digits[..n as usize]);
+        out.push('.');
+        out.push_str(&digits[n as usize..]);
+    } else if -6 < n && n <= 0 {
+        out.push_str("0.");
+        out.extend((n..0).map(|_| '0'));
+        out.push_str(digits);
+    } else {
+        out.push_str(&digits[..1]);
+        if k > 1 {
+            out.push('.');
+            out.push_str(&digits[1..]);
+        }
+        let exp = n - 1;
+        write!(out, "e{}{}", if exp > 0 { '+' } else { '-' }, exp.abs())?;
+    }
+    Ok(())
+}
+
+/// The string an object key is written as, if it is a valid key.
+fn key_string<K: ?Sized + Serialize>(key: &K) -> Result<String, Error> {
+    key.serialize(KeyString)
+}
+
+/// Serializes an object key into the string it is written as, the same as `serde_json` does.
+struct KeyString;
+
+impl KeyString {
+    fn display<T: ?Sized + std::fmt::Display>(value: &T) -> Result<String, Error> {
+        let mut s = String::new();
+        write!(s, "{}", value)?;
+        Ok(s)
+    }
+
+    fn number(value: f64) -> Result<String, Error> {
+        let mut s = String::new();
+        write_number(&mut s, value)?;
+        Ok(s)
+    }
+}
+
+impl ser::Serializer for KeyString {
+    type Ok = String;
+    type Error = Error;
+
+    type SerializeSeq = ser::Impossible<String, Error>;
+    type SerializeTuple = ser::Impossible<String, Error>;
+    type SerializeTupleStruct = ser::Impossible<String, Error>;
+    type SerializeTupleVariant = ser::Impossible<String, Error>;
+    type SerializeMap = ser::Impossible<String, Error>;
+    type SerializeStruct = ser::Impossible<String, Error>;
+    type SerializeStructVariant = ser::Impossible<String, Error>;
+
+    fn serialize_bool(self, value: bool) -> Result<String, Error> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<String, Error> {
+        Self::display(&value)
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<String, Error> {
+        Self::display(&value)
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<String, Error> {
+        Self::display(&value)
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<String, Error> {
+        Self::display(&value)
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<String, Error> {
+        Self::display(&value)
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<String, Error> {
+        Self::display(&value)
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<String, Error> {
+        Self::display(&value)
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<String, Error> {
+        Self::display(&value)
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<String, Error> {
+        Self::display(&value)
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<String, Error> {
+        Self::display(&value)
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<String, Error> {
+        Self::number(value.into())
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<String, Error> {
+        Self::number(value)
+    }
+
+    fn serialize_char(self, value: char) -> Result<String, Error> {
+        Ok(value.to_string())
+    }
+
+    fn serialize_str(self, value: &str) -> Result<String, Error> {
+        Ok(value.to_owned())
+    }
+
+    fn serialize_bytes(self, _value: &[u8]) -> Result<String, Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn serialize_none(self) -> Result<String, Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<String, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String, Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, Error> {
+        Ok(variant.to_owned())
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<String, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn collect_str<T>(self, value: &T) -> Result<String, Error>
+    where
+        T: ?Sized + std::fmt::Display,
+    {
+        Self::display(value)
+    }
+}
+
+impl<'a> ser::Serializer for Canonical<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Seq<'a>;
+    type SerializeTuple = Seq<'a>;
+    type SerializeTupleStruct = Seq<'a>;
+    type SerializeTupleVariant = Seq<'a>;
+    type SerializeMap = Object<'a>;
+    type SerializeStruct = Object<'a>;
+    type SerializeStructVariant = Object<'a>;
+
+    fn serialize_bool(self, value: bool) -> Result<(), Error> {
+        self.0.push_str(if value { "true" } else { "false" });
+        Ok(())
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<(), Error> {
+        write_int(self.0, value.into())
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<(), Error> {
+        write_int(self.0, value.into())
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<(), Error> {
+        write_int(self.0, value.into())
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<(), Error> {
+        write_int(self.0, value.into())
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<(), Error> {
+        write_int(self.0, value)
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<(), Error> {
+        write_int(self.0, value.into())
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<(), Error> {
+        write_int(self.0, value.into())
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<(), Error> {
+        write_int(self.0, value.into())
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<(), Error> {
+        write_int(self.0, value.into())
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<(), Error> {
+        match i128::try_from(value) {
+            Ok(value) => write_int(self.0, value),
+            Err(_) => write_number(self.0, value as f64),
+        }
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<(), Error> {
+        write_number(self.0, value.into())
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<(), Error> {
+        write_number(self.0, value)
+    }
+
+    fn serialize_char(self, value: char) -> Result<(), Error> {
+        self.serialize_str(value.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<(), Error> {
+        Ok(write_str(self.0, value)?)
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<(), Error> {
+        use serde::ser::SerializeSeq;
+
+        let mut seq = self.serialize_seq(Some(value.len()))?;
+        for byte in value {
+            seq.serialize_element(byte)?;
+        }
+        seq.end()
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.0.push_str("null");
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        // a single key is already sorted
+        self.0.push('{');
+        write_str(self.0, variant)?;
+        self.0.push(':');
+        value.serialize(Canonical(&mut *self.0))?;
+        self.0.push('}');
+        Ok(())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Seq<'a>, Error> {
+        self.0.push('[');
+        Ok(Seq {
+            out: self.0,
+            first: true,
+            variant: false,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Seq<'a>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Seq<'a>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Seq<'a>, Error> {
+        self.0.push('{');
+        write_str(self.0, variant)?;
+        self.0.push_str(":[");
+        Ok(Seq {
+            out: self.0,
+            first: true,
+            variant: true,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Object<'a>, Error> {
+        Ok(Object::new(self.0, false))
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Object<'a>, Error> {
+        let mut object = Object::new(self.0, false);
+        object.raw = name == TOKEN;
+        Ok(object)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Object<'a>, Error> {
+        self.0.push('{');
+        write_str(self.0, variant)?;
+        self.0.push(':');
+        Ok(Object::new(self.0, true))
+    }
+
+    fn collect_str<T>(self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + core::fmt::Display,
+    {
+        self.serialize_str(&value.to_string())
+    }
+}
+
+/// An array, or the array of a tuple variant.
+struct Seq<'a> {
+    out: &'a mut String,
+    first: bool,
+    /// Whether to close the object around a variant too.
+    variant: bool,
+}
+
+impl ser::SerializeSeq for Seq<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if !core::mem::replace(&mut self.first, false) {
+            self.out.push(',');
+        }
+        value.serialize(Canonical(&mut *self.out))
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.out.push(']');
+        if self.variant {
+            self.out.push('}');
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for Seq<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for Seq<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for Seq<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+/// An object, whose entries are kept until the end to be sorted.
+struct Object<'a> {
+    out: &'a mut String,
+    /// Each key, and its value as canonical JSON.
+    entries: Vec<(String, String)>,
+    /// Whether to close the object around a variant too.
+    variant: bool,
+    /// Whether this is a [`Raw`](crate::Raw) fragment instead.
+    raw: bool,
+}
+
+impl<'a> Object<'a> {
+    fn new(out: &'a mut String, variant: bool) -> Self {
+        Object {
+            out,
+            entries: Vec::new(),
+            variant,
+            raw: false,
+        }
+    }
+}
+
+impl ser::SerializeMap for Object<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.entries.push((key_string(key)?, String::new()));
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        match self.entries.last_mut() {
+            Some((_, out)) => value.serialize(Canonical(out)),
+            None => Err(Error::custom("serialize_value called before serialize_key")),
+        }
+    }
+
+    fn end(mut self) -> Result<(), Error> {
+        self.entries
+            .sort_unstable_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+        // RFC 8785 only takes I-JSON, where the keys of an object are unique
+        if let Some(pair) = self.entries.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(Error::custom(format_args!(
+                "duplicate key `{}` in object",
+                pair[0].0
+            )));
+        }
+
+        self.out.push('{');
+        for (i, (key, value)) in self.entries.iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            write_str(self.out, key)?;
+            self.out.push(':');
+            self.out.push_str(value);
+        }
+        self.out.push('}');
+        if self.variant {
+            self.out.push('}');
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for Object<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if self.raw {
+            // parse the fragment, which may not be canonical
+            let json = match serde_json::to_value(value) {
+                Ok(serde_json::Value::String(json)) => json,
+                _ => return Err(Error::custom("expected a string of raw JSON")),
+            };
+            let value: serde_json::Value = serde_json::from_str(&json).map_err(Error::custom)?;
+            return value.serialize(Canonical(&mut *self.out));
+        }
+        ser::SerializeMap::serialize_entry(self, key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        if self.raw {
+            return Ok(());
+        }
+        ser::SerializeMap::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for Object<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeMap::serialize_entry(self, key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        ser::SerializeMap::end(self)
+    }
+}
@@ -0,0 +1,101 @@
+/// The case that `#![rename_all = "..."]` converts shorthand keys to.
+///
+/// Identifiers are assumed to be `snake_case`, the same as serde's `rename_all`.
+#[doc(hidden)]
+#[derive(Clone, Copy)]
+pub enum Case {
+    /// Leave the identifier as written.
+    None,
+    /// `lowercase`
+    Lower,
+    /// `UPPERCASE`
+    Upper,
+    /// `PascalCase`
+    Pascal,
+    /// `camelCase`
+    Camel,
+    /// `snake_case`
+    Snake,
+    /// `SCREAMING_SNAKE_CASE`
+    ScreamingSnake,
+    /// `kebab-case`
+    Kebab,
+    /// `SCREAMING-KEBAB-CASE`
+    ScreamingKebab,
+}
+
+/// A key converted at compile time by [`Case::convert`].
+#[doc(hidden)]
+pub struct Renamed<const N: usize>([u8; N]);
+
+impl<const N: usize> Renamed<N> {
+    pub const fn as_str(&'static self) -> &'static str {
+        // SAFETY: `Case::convert` only changes the case of, or replaces, ASCII bytes
+        // in a valid `str`, so the result is still valid UTF-8.
+        unsafe { core::str::from_utf8_unchecked(&self.0) }
+    }
+}
+
+impl Case {
+    /// Strip the `r#` prefix from raw identifiers
+    const fn ident(ident: &str) -> &[u8] {
+        let bytes = ident.as_bytes();
+        match bytes {
+            [b'r', b'#', rest @ ..] => rest,
+            _ => bytes,
+        }
+    }
+
+    /// The length of `ident` once converted
+    pub const fn len(self, ident: &str) -> usize {
+        let ident = Case::ident(ident);
+        match self {
+            Case::Pascal | Case::Camel => {
+                let mut len = 0;
+                let mut i = 0;
+                while i < ident.len() {
+                    if ident[i] != b'_' {
+                        len += 1;
+                    }
+                    i += 1;
+                }
+                len
+            }
+            _ => ident.len(),
+        }
+    }
+
+    /// Convert `ident` to this case. `N` must be `self.len(ident)`
+    pub const fn convert<const N: usize>(self, ident: &str) -> Renamed<N> {
+        let ident = Case::ident(ident);
+        let mut out = [0; N];
+        let mut i = 0;
+        let mut j = 0;
+        let mut capitalize = matches!(self, Case::Pascal);
+        while i < ident.len() {
+            let b = ident[i];
+            i += 1;
+            match self {
+                Case::None | Case::Snake => out[j] = b,
+                Case::Lower => out[j] = b.to_ascii_lowercase(),
+                Case::Upper | Case::ScreamingSnake => out[j] = b.to_ascii_uppercase(),
+                Case::Kebab if b == b'_' => out[j] = b'-',
+                Case::Kebab => out[j] = b,
+                Case::ScreamingKebab if b == b'_' => out[j] = b'-',
+                Case::ScreamingKebab => out[j] = b.to_ascii_uppercase(),
+                Case::Pascal | Case::Camel if b == b'_' => {
+                    // only capitalize words that follow another word
+                    capitalize = matches!(self, Case::Pascal) || j > 0;
+                    continue;
+                }
+                Case::Pascal | Case::Camel if capitalize => {
+                    capitalize = false;
+                    out[j] = b.to_ascii_uppercase();
+                }
+                Case::Pascal | Case::Camel => out[j] = b,
+            }
+            j += 1;
+        }
+        Renamed(out)
+    }
+}
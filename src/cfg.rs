@@ -0,0 +1,22 @@
+//! The type of an object entry with `#[cfg(...)]` attributes, for `json_type!`.
+//!
+//! An entry that is compiled out of `json!` leaves a `()` in its place, so the type picks between
+//! the entry and `()` on the value of `cfg!(...)` for the same predicates.
+
+/// Whether the predicates of an entry's `#[cfg(...)]` attributes hold.
+#[doc(hidden)]
+pub struct Cfg<const ENABLED: bool>;
+
+/// The type of an entry of type `T`, given whether it is compiled in.
+#[doc(hidden)]
+pub trait Select<T> {
+    type Type;
+}
+
+impl<T> Select<T> for Cfg<true> {
+    type Type = T;
+}
+
+impl<T> Select<T> for Cfg<false> {
+    type Type = ();
+}
@@ -0,0 +1,92 @@
+use serde::ser::{Serialize, Serializer};
+
+use crate::raw::Parsed;
+use crate::Raw;
+
+/// A JSON document rendered at compile time by [`json_const!`](crate::json_const), in both
+/// compact and pretty form.
+///
+/// It serializes through the same path as [`Raw`], so `serde_json` and this crate's
+/// [`Serializer`](crate::Serializer) write the compact JSON verbatim. Other serializers see a
+/// struct holding the text instead, so serialize [`ConstJson::parsed`] for those.
+///
+/// [`Display`](core::fmt::Display) writes the compact JSON, or the pretty JSON with `{:#}`.
+/// Like a [`json!`](crate::json) value, `{:#4}`, `{:\t<#}` and `{:#.1}` change the indent and
+/// how many levels are broken across lines, which writes the document out value by value.
+///
+/// ```
+/// # use typed_json::{json, ConstJson};
+/// #
+/// const NOT_FOUND: ConstJson = ConstJson::new_unchecked(
+///     r#"{"error":"not found"}"#,
+///     "{\n  \"error\": \"not found\"\n}",
+/// );
+///
+/// let body = json!({ "status": 404, "body": NOT_FOUND });
+/// assert_eq!(
+///     serde_json::to_string(&body).unwrap(),
+///     r#"{"status":404,"body":{"error":"not found"}}"#,
+/// );
+/// assert_eq!(format!("{:#}", NOT_FOUND), "{\n  \"error\": \"not found\"\n}");
+/// assert_eq!(format!("{:#4}", NOT_FOUND), "{\n    \"error\": \"not found\"\n}");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ConstJson {
+    compact: &'static str,
+    pretty: &'static str,
+}
+
+impl ConstJson {
+    /// Wrap a document without checking that both forms are valid JSON, or that they are the
+    /// same document.
+    ///
+    /// This is not unsafe, but if `compact` is not a single valid JSON value then neither is
+    /// the output it is spliced into.
+    #[inline]
+    pub const fn new_unchecked(compact: &'static str, pretty: &'static str) -> Self {
+        ConstJson { compact, pretty }
+    }
+
+    /// The JSON with no whitespace, the same as `serde_json::to_string`.
+    #[inline]
+    pub const fn compact(&self) -> &'static str {
+        self.compact
+    }
+
+    /// The JSON indented by two spaces, the same as `serde_json::to_string_pretty`.
+    #[inline]
+    pub const fn pretty(&self) -> &'static str {
+        self.pretty
+    }
+
+    /// The compact JSON as a [`Raw`] fragment.
+    #[inline]
+    pub const fn raw(&self) -> Raw<'static> {
+        Raw::new_unchecked(self.compact)
+    }
+
+    /// The document serialized value by value, for serializers that don't splice in [`Raw`].
+    #[inline]
+    pub const fn parsed(&self) -> Parsed<'static> {
+        self.raw().parsed()
+    }
+}
+
+impl Serialize for ConstJson {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.raw().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'de> crate::Deserializer<'de> for ConstJson {
+    fn deserialize_any2<V>(self, visitor: V) -> Result<V::Value, serde::de::value::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        crate::Deserializer::deserialize_any2(self.raw(), visitor)
+    }
+}
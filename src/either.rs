@@ -0,0 +1,96 @@
+use serde::de::Visitor;
+use serde::ser::{Serialize, Serializer};
+
+/// One of two JSON values, chosen at runtime by an `if` or a `match` inside
+/// [`json!`](crate::json).
+///
+/// `if cond { a } else { b }` is `Left(a)` or `Right(b)`. An `else if` or a
+/// `match` with more arms nests to the right, so the third of three arms is
+/// `Right(Right(c))`, of type `Either<A, Either<B, C>>`.
+///
+/// ```
+/// # use typed_json::json;
+/// #
+/// let result: Result<u32, &str> = Err("not found");
+///
+/// let value = json!({
+///     "result": match result {
+///         Ok(value) => { "value": value },
+///         Err(error) => { "error": error },
+///     },
+/// });
+///
+/// assert_eq!(
+///     serde_json::to_string(&value).unwrap(),
+///     r#"{"result":{"error":"not found"}}"#,
+/// );
+/// ```
+#[derive(Clone, Copy)]
+pub enum Either<L, R> {
+    /// The first branch.
+    Left(L),
+    /// Any of the branches after the first.
+    Right(R),
+}
+
+impl<L: Serialize, R: Serialize> Serialize for Either<L, R> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Either::Left(l) => l.serialize(serializer),
+            Either::Right(r) => r.serialize(serializer),
+        }
+    }
+}
+
+impl<'de, L, R> crate::Deserializer<'de> for Either<L, R>
+where
+    L: crate::Deserializer<'de>,
+    R: crate::Deserializer<'de>,
+{
+    fn deserialize_any2<V>(self, visitor: V) -> Result<V::Value, serde::de::value::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Either::Left(l) => l.deserialize_any2(visitor),
+            Either::Right(r) => r.deserialize_any2(visitor),
+        }
+    }
+}
+
+impl<'de, L, R> serde::de::Deserializer<'de> for Either<L, R>
+where
+    L: serde::de::Deserializer<'de, Error = serde::de::value::Error>,
+    R: serde::de::Deserializer<'de, Error = serde::de::value::Error>,
+{
+    type Error = serde::de::value::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Either::Left(l) => l.deserialize_any(visitor),
+            Either::Right(r) => r.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Either::Left(l) => l.deserialize_option(visitor),
+            Either::Right(r) => r.deserialize_option(visitor),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
@@ -1,5 +1,7 @@
 use serde::{de::Visitor, forward_to_deserialize_any, Deserializer};
 
+/// A Rust value in a [`json!`](crate::json) literal. Every value other than `null`, an array
+/// or an object is one, whether it was written as a literal or interpolated.
 #[derive(Clone, Copy)]
 pub struct Expr<T>(pub T);
 
@@ -1,43 +1,148 @@
-use std::fmt;
-use std::io;
+use core::fmt;
 
 use serde::Serialize;
 
 use crate::array::Array;
-use crate::array::ItemSer;
-use crate::map::KeyValuePairSer;
+use crate::array::Comprehension;
+use crate::array::{ItemFmt, ItemSer};
+use crate::exact_len;
 use crate::map::Map;
+use crate::map::{KeyValuePairFmt, KeyValuePairSer};
+use crate::ser::{Compact, Escaping, Formatter, Pretty, Serializer};
+use crate::ConstJson;
+use crate::Either;
 use crate::Expr;
 use crate::Null;
 
-struct WriterFormatter<'a, 'b: 'a> {
-    inner: &'a mut fmt::Formatter<'b>,
+/// Write `value` as compact JSON, or pretty-printed with `{:#}`.
+///
+/// The pretty format takes its indent from the fill and width, so `{:#4}` indents by four spaces
+/// and `{:\t<#}` by a tab, and the precision is how many levels are broken across lines.
+fn display<T: ?Sized + Serialize>(value: &T, f: &mut fmt::Formatter) -> fmt::Result {
+    if f.alternate() {
+        // {:#}
+        let mut fill = [0; 4];
+        let fill = f.fill().encode_utf8(&mut fill);
+        let repeat = match f.width() {
+            Some(width) => width,
+            None if fill == " " => 2,
+            None => 1,
+        };
+        let mut pretty = Pretty::new().indent_repeat(fill, repeat);
+        if let Some(depth) = f.precision() {
+            pretty = pretty.depth(depth);
+        }
+        value.serialize(&mut Serializer::with_formatter(f, pretty))?;
+    } else {
+        // {}
+        value.serialize(&mut Serializer::new(f))?;
+    }
+    Ok(())
+}
+
+/// Displays a value as JSON laid out by a [`Formatter`], from [`Pretty::display`] or the
+/// `display` method of a [`json!`](crate::json) value.
+///
+/// Strings can be escaped beyond what JSON requires, for output that is inlined into HTML or
+/// sent over a channel that only takes ASCII:
+///
+/// ```
+/// # use typed_json::json;
+/// #
+/// let name = "Zoë </script>";
+/// let json = json!({ "name": name });
+///
+/// assert_eq!(json.display().html_safe().to_string(),
+///     r#"{"name":"Zoë \u003c/script\u003e"}"#);
+/// assert_eq!(json.display().ascii_only().to_string(),
+///     r#"{"name":"Zo\u00eb </script>"}"#);
+/// assert_eq!(json.display().pretty().html_safe().ascii_only().to_string(),
+///     "{\n  \"name\": \"Zo\\u00eb \\u003c/script\\u003e\"\n}");
+/// ```
+pub struct Formatted<'a, T: ?Sized, F> {
+    value: &'a T,
+    formatter: F,
+    escaping: Escaping,
 }
 
-impl<'a, 'b> io::Write for WriterFormatter<'a, 'b> {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        // Safety: the serializer below only emits valid utf8 when using
-        // the default formatter.
-        // > Serialization guarantees it only feeds valid UTF-8 sequences to the writer.
-        // <https://docs.rs/serde_json/1.0.108/serde_json/fn.to_writer.html>
-        // <https://docs.rs/serde_json/1.0.108/serde_json/fn.to_writer_pretty.html>
-        let s = unsafe { std::str::from_utf8_unchecked(buf) };
-        self.inner.write_str(s).map_err(io_error)?;
-        Ok(buf.len())
+impl<'a, T: ?Sized, F> Formatted<'a, T, F> {
+    pub(crate) fn new(value: &'a T, formatter: F) -> Self {
+        Formatted {
+            value,
+            formatter,
+            escaping: Escaping::default(),
+        }
+    }
+
+    /// Pretty-print the JSON, indented by two spaces, the same as `{:#}`.
+    pub fn pretty(self) -> Formatted<'a, T, Pretty<'static>> {
+        self.with_formatter(Pretty::new())
+    }
+
+    /// Lay out the JSON with `formatter`.
+    pub fn with_formatter<G>(self, formatter: G) -> Formatted<'a, T, G> {
+        Formatted {
+            value: self.value,
+            formatter,
+            escaping: self.escaping,
+        }
+    }
+
+    /// Escape strings for HTML, as with [`Serializer::html_safe`].
+    pub fn html_safe(mut self) -> Self {
+        self.escaping.html = true;
+        self
     }
 
-    fn flush(&mut self) -> io::Result<()> {
-        Ok(())
+    /// Escape strings down to ASCII, as with [`Serializer::ascii_only`].
+    pub fn ascii_only(mut self) -> Self {
+        self.escaping.ascii = true;
+        self
+    }
+}
+
+impl<T: ?Sized + Serialize, F: Formatter + Clone> fmt::Display for Formatted<'_, T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut ser =
+            Serializer::with_formatter(f, self.formatter.clone()).with_escaping(self.escaping);
+        Ok(ser.write_value(self.value)?)
     }
 }
 
-fn io_error(_: fmt::Error) -> io::Error {
-    // Error value does not matter because Display impl just maps it
-    // back to fmt::Error.
-    io::Error::new(io::ErrorKind::Other, "fmt error")
+/// Adds `display`, which returns a [`Formatted`] to pick the layout and escaping of the JSON.
+macro_rules! display_method {
+    ($($impl:tt)*) => {
+        $($impl)* {
+            /// Display the value as compact JSON, with options for the layout and escaping.
+            ///
+            /// See [`Formatted`].
+            pub fn display(&self) -> Formatted<'_, Self, Compact> {
+                Formatted::new(self, Compact)
+            }
+        }
+    };
+}
+
+display_method!(impl<T: KeyValuePairSer> Map<T>);
+display_method!(impl<T: ItemSer> Array<T>);
+display_method!(impl<I, F, T> Comprehension<I, F> where I: IntoIterator + Clone, F: Fn(I::Item) -> T, T: Serialize);
+display_method!(impl<S: Serialize> Expr<S>);
+display_method!(impl Null);
+display_method!(impl<L: Serialize, R: Serialize> Either<L, R>);
+display_method!(impl ConstJson);
+
+impl fmt::Display for ConstJson {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (f.alternate(), f.fill(), f.width(), f.precision()) {
+            (false, ..) => f.write_str(self.compact()),
+            (true, ' ', None, None) => f.write_str(self.pretty()),
+            // an indent or depth that the rendered pretty JSON doesn't have
+            _ => display(&self.parsed(), f),
+        }
+    }
 }
 
-impl<T: KeyValuePairSer> fmt::Display for Map<T> {
+impl<T: KeyValuePairSer + KeyValuePairFmt> fmt::Display for Map<T> {
     /// Display a JSON value as a string.
     ///
     /// ```
@@ -62,20 +167,35 @@ impl<T: KeyValuePairSer> fmt::Display for Map<T> {
     /// assert_eq!(pretty,
     ///     "{\n  \"city\": \"London\",\n  \"street\": \"10 Downing Street\"\n}");
     /// ```
+    ///
+    /// The width and fill set the indent, and the precision how many levels are pretty-printed:
+    ///
+    /// ```
+    /// # use typed_json::json;
+    /// #
+    /// let json = json!({ "name": "London", "zones": [1, 2] });
+    ///
+    /// assert_eq!(format!("{:#4}", json),
+    ///     "{\n    \"name\": \"London\",\n    \"zones\": [\n        1,\n        2\n    ]\n}");
+    /// assert_eq!(format!("{:\t<#}", json),
+    ///     "{\n\t\"name\": \"London\",\n\t\"zones\": [\n\t\t1,\n\t\t2\n\t]\n}");
+    /// assert_eq!(format!("{:#.1}", json),
+    ///     "{\n  \"name\": \"London\",\n  \"zones\": [1,2]\n}");
+    /// ```
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let alternate = f.alternate();
-        let mut wr = WriterFormatter { inner: f };
-        if alternate {
-            // {:#}
-            serde_json::ser::to_writer_pretty(&mut wr, self).map_err(|_| fmt::Error)
-        } else {
-            // {}
-            serde_json::ser::to_writer(&mut wr, self).map_err(|_| fmt::Error)
+        if f.alternate() {
+            return display(self, f);
         }
+        // {}, with each value written by its own `Display`, so that the keys rendered by
+        // `json!` are written as is all the way down, where the length isn't needed
+        let mut ser = Serializer::new(f);
+        let mut map = serde::Serializer::serialize_map(&mut ser, None)?;
+        self.0.fmt(&mut map)?;
+        Ok(serde::ser::SerializeMap::end(map)?)
     }
 }
 
-impl<T: ItemSer> fmt::Display for Array<T> {
+impl<T: ItemSer + ItemFmt> fmt::Display for Array<T> {
     /// Display a JSON value as a string.
     ///
     /// ```
@@ -101,15 +221,64 @@ impl<T: ItemSer> fmt::Display for Array<T> {
     ///     "[\n  \"London\",\n  \"10 Downing Street\"\n]");
     /// ```
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let alternate = f.alternate();
-        let mut wr = WriterFormatter { inner: f };
-        if alternate {
-            // {:#}
-            serde_json::ser::to_writer_pretty(&mut wr, self).map_err(|_| fmt::Error)
-        } else {
-            // {}
-            serde_json::ser::to_writer(&mut wr, self).map_err(|_| fmt::Error)
+        if f.alternate() {
+            return display(self, f);
+        }
+        // {}, the same as a `Map`, where the length isn't needed
+        let mut ser = Serializer::new(f);
+        let mut seq = serde::Serializer::serialize_seq(&mut ser, None)?;
+        self.0.fmt(&mut seq)?;
+        Ok(serde::ser::SerializeSeq::end(seq)?)
+    }
+}
+
+impl<I, F, T> fmt::Display for Comprehension<I, F>
+where
+    I: IntoIterator + Clone,
+    F: Fn(I::Item) -> T,
+    T: Serialize + fmt::Display,
+{
+    /// Display a JSON value as a string.
+    ///
+    /// ```
+    /// # use typed_json::json;
+    /// #
+    /// let json = json!([for x in 1..3 => { "x": x }]);
+    ///
+    /// // Compact format:
+    /// //
+    /// // [{"x":1},{"x":2}]
+    /// let compact = format!("{}", json);
+    /// assert_eq!(compact,
+    ///     "[{\"x\":1},{\"x\":2}]");
+    ///
+    /// // Pretty format:
+    /// //
+    /// // [
+    /// //   {
+    /// //     "x": 1
+    /// //   },
+    /// //   {
+    /// //     "x": 2
+    /// //   }
+    /// // ]
+    /// let pretty = format!("{:#}", json);
+    /// assert_eq!(pretty,
+    ///     "[\n  {\n    \"x\": 1\n  },\n  {\n    \"x\": 2\n  }\n]");
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            return display(self, f);
         }
+        // {}, the same as a `Map`
+        let iter = self.iter.clone().into_iter();
+        let mut ser = Serializer::new(f);
+        let mut seq = serde::Serializer::serialize_seq(&mut ser, exact_len(&iter))?;
+        for item in iter {
+            let value = (self.f)(item);
+            seq.element_with(|f| value.fmt(f))?;
+        }
+        Ok(serde::ser::SerializeSeq::end(seq)?)
     }
 }
 
@@ -130,15 +299,7 @@ impl<S: Serialize> fmt::Display for Expr<S> {
     /// assert_eq!(pretty, "1");
     /// ```
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let alternate = f.alternate();
-        let mut wr = WriterFormatter { inner: f };
-        if alternate {
-            // {:#}
-            serde_json::ser::to_writer_pretty(&mut wr, self).map_err(|_| fmt::Error)
-        } else {
-            // {}
-            serde_json::ser::to_writer(&mut wr, self).map_err(|_| fmt::Error)
-        }
+        display(self, f)
     }
 }
 
@@ -159,14 +320,26 @@ impl fmt::Display for Null {
     /// assert_eq!(pretty, "null");
     /// ```
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let alternate = f.alternate();
-        let mut wr = WriterFormatter { inner: f };
-        if alternate {
-            // {:#}
-            serde_json::ser::to_writer_pretty(&mut wr, self).map_err(|_| fmt::Error)
-        } else {
-            // {}
-            serde_json::ser::to_writer(&mut wr, self).map_err(|_| fmt::Error)
+        display(self, f)
+    }
+}
+
+impl<L: fmt::Display, R: fmt::Display> fmt::Display for Either<L, R> {
+    /// Display the JSON value of the branch that was taken.
+    ///
+    /// ```
+    /// # use typed_json::json;
+    /// #
+    /// let admin = true;
+    /// let json = json!(if admin { ["read", "write"] } else { ["read"] });
+    ///
+    /// assert_eq!(format!("{}", json), r#"["read","write"]"#);
+    /// assert_eq!(format!("{:#}", json), "[\n  \"read\",\n  \"write\"\n]");
+    /// ```
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Either::Left(l) => l.fmt(f),
+            Either::Right(r) => r.fmt(f),
         }
     }
 }
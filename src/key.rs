@@ -0,0 +1,87 @@
+use core::fmt;
+
+use serde::Serialize;
+
+use crate::{DuplicateKey, Either, Expr, Fmt};
+
+/// A type that can be used as the key of an object in [`json!`](crate::json).
+///
+/// JSON only allows string keys, so a key must serialize as a string, or as a
+/// number or char that JSON serializers write as a string. Using any other type
+/// as a key is a compile error, rather than failing at runtime.
+///
+/// ```compile_fail
+/// # use typed_json::json;
+/// #
+/// let key = vec!["not", "a", "key"];
+/// let value = json!({ key: 1 });
+/// ```
+///
+/// The trait is sealed, so that every key is one of the types below and its serialization
+/// can't fail for being a key. A key of another type can be formatted into one with `f!`.
+///
+/// ```
+/// # use typed_json::json;
+/// # use std::fmt;
+/// #
+/// struct UserId(u64);
+///
+/// impl fmt::Display for UserId {
+///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         write!(f, "u{}", self.0)
+///     }
+/// }
+///
+/// let id = UserId(123);
+/// let value = json!({ f!("{}", id): "john" });
+///
+/// assert_eq!(serde_json::to_string(&value).unwrap(), r#"{"u123":"john"}"#);
+/// ```
+pub trait JsonKey: Serialize + private::Sealed {}
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Seals and implements [`JsonKey`] for each type.
+macro_rules! json_key {
+    ($(impl$([$($params:tt)*])? for $ty:ty $(where [$($bounds:tt)*])?;)*) => {$(
+        impl$(<$($params)*>)? private::Sealed for $ty $(where $($bounds)*)? {}
+        impl$(<$($params)*>)? JsonKey for $ty $(where $($bounds)*)? {}
+    )*};
+}
+
+json_key! {
+    impl[T: ?Sized + JsonKey] for &T;
+
+    impl for str;
+    impl for char;
+    impl['a] for fmt::Arguments<'a>;
+
+    impl for i8;
+    impl for i16;
+    impl for i32;
+    impl for i64;
+    impl for i128;
+    impl for isize;
+    impl for u8;
+    impl for u16;
+    impl for u32;
+    impl for u64;
+    impl for u128;
+    impl for usize;
+
+    impl[T: JsonKey] for Expr<T>;
+    impl[L: JsonKey, R: JsonKey] for Either<L, R>;
+
+    impl[F] for Fmt<F> where [F: Fn(&mut fmt::Formatter<'_>) -> fmt::Result];
+
+    // never a value, but a key, so that a duplicate key is reported once as a type mismatch
+    impl for DuplicateKey;
+}
+
+#[cfg(feature = "std")]
+json_key! {
+    impl for String;
+    impl['a] for std::borrow::Cow<'a, str>;
+}
@@ -2,24 +2,60 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 #![cfg_attr(docsrs, feature(doc_auto_cfg))]
 
-use expr_de::Expr;
+pub use array::{Array, Comprehension};
+#[cfg(feature = "std")]
+pub use canonical::to_canonical_string;
+pub use const_json::ConstJson;
+pub use either::Either;
+pub use expr_de::Expr;
+pub use fmt::Formatted;
+pub use key::JsonKey;
+pub use map::{Map, KV};
+pub use raw::{InvalidJson, Parsed, Raw};
+pub use ser::{
+    to_writer, to_writer_pretty, to_writer_width, Compact, Error, Formatter, Lookahead, Pretty,
+    Serializer,
+};
+#[cfg(feature = "std")]
+pub use strict::Strict;
+pub use unique::DuplicateKey;
 
 #[macro_use]
 mod macros;
 mod expr_de;
 
-#[cfg(feature = "std")]
 mod fmt;
 
 mod array;
+#[cfg(feature = "std")]
+mod canonical;
+mod case;
+mod cfg;
+mod const_json;
+mod either;
+mod key;
 mod map;
+mod raw;
+mod ser;
+mod spread;
+#[cfg(feature = "std")]
+mod strict;
+mod unique;
 
 #[doc(hidden)]
 pub mod __private {
     pub use crate::array::Array;
+    pub use crate::case::{Case, Renamed};
+    pub use crate::cfg::{Cfg, Select};
     pub use crate::expr_de::Expr;
-    pub use crate::map::{Map, KV};
-    pub use crate::Null;
+    pub use crate::map::{Entries, Map, Rendered, KV};
+    pub use crate::raw::{is_valid, Json, Valid};
+    pub use crate::spread::Spread;
+    pub use crate::unique::{contains, Key, Unique};
+    pub use crate::{Fmt, Null};
+
+    #[cfg(feature = "macros")]
+    pub use typed_json_macros::{include_json, json, json_const, json_str, json_type};
 }
 
 /// A clone of [`serde::de::Deserializer`] to get around the orphan rule
@@ -79,6 +115,45 @@ impl serde::ser::Serialize for Null {
     }
 }
 
+/// `f!("...", args)` inside a `json!` literal.
+///
+/// Writes the formatted string with [`Serializer::collect_str`](serde::Serializer::collect_str),
+/// so it never needs to allocate a `String`.
+#[derive(Clone, Copy)]
+#[doc(hidden)]
+pub struct Fmt<F>(F);
+
+impl<F> Fmt<F>
+where
+    F: Fn(&mut core::fmt::Formatter<'_>) -> core::fmt::Result,
+{
+    #[inline]
+    pub fn new(f: F) -> Self {
+        Fmt(f)
+    }
+}
+
+impl<F> core::fmt::Display for Fmt<F>
+where
+    F: Fn(&mut core::fmt::Formatter<'_>) -> core::fmt::Result,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        (self.0)(f)
+    }
+}
+
+impl<F> serde::ser::Serialize for Fmt<F>
+where
+    F: Fn(&mut core::fmt::Formatter<'_>) -> core::fmt::Result,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
 trait DeShared {
     fn is_done(&self) -> bool;
 }
@@ -105,6 +180,14 @@ impl DeShared for () {
     }
 }
 
+/// The length of the iterator, if it is known exactly.
+fn exact_len<I: Iterator>(iter: &I) -> Option<usize> {
+    match iter.size_hint() {
+        (lower, Some(upper)) if lower == upper => Some(lower),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::BTreeMap;
@@ -117,6 +200,13 @@ mod tests {
         foo: i32,
     }
 
+    #[derive(Debug, Deserialize)]
+    struct Optional {
+        foo: i32,
+        bar: Option<i32>,
+        baz: Option<i32>,
+    }
+
     #[test]
     fn object() {
         let data = json!({"foo": 123});
@@ -139,6 +229,284 @@ mod tests {
         );
     }
 
+    #[test]
+    fn object_optional() {
+        let data = json!({"foo": 123, "bar"?: None::<i32>, "baz"?: Some(456)});
+        let x = Optional::deserialize(data).unwrap();
+        let y = <BTreeMap<String, i32>>::deserialize(data).unwrap();
+        assert_eq!(x.foo, 123);
+        assert_eq!(x.bar, None);
+        assert_eq!(x.baz, Some(456));
+        assert_eq!(y.len(), 2);
+    }
+
+    #[test]
+    fn object_optional_ser() {
+        serde_test::assert_ser_tokens(
+            &json!({"foo": 123, "bar"?: None::<i32>, "baz"?: Some(456)}),
+            &[
+                Token::Map { len: Some(2) },
+                Token::Str("foo"),
+                Token::I32(123),
+                Token::Str("baz"),
+                Token::I32(456),
+                Token::MapEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn object_conditional() {
+        let yes = true;
+        let data = json!({"foo": 123, if !yes => "bar": 1, if yes => "baz"?: Some(456)});
+        let x = Optional::deserialize(data).unwrap();
+        assert_eq!(x.foo, 123);
+        assert_eq!(x.bar, None);
+        assert_eq!(x.baz, Some(456));
+    }
+
+    #[test]
+    fn object_conditional_ser() {
+        let yes = true;
+        serde_test::assert_ser_tokens(
+            &json!({
+                if yes => "foo": [1],
+                if !yes => "bar": {},
+                "baz": if yes { 1 } else { 2 },
+            }),
+            &[
+                Token::Map { len: Some(2) },
+                Token::Str("foo"),
+                Token::Seq { len: Some(1) },
+                Token::I32(1),
+                Token::SeqEnd,
+                Token::Str("baz"),
+                Token::I32(1),
+                Token::MapEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn object_spread_ser() {
+        let mut extra = BTreeMap::new();
+        extra.insert("bar", 456);
+        serde_test::assert_ser_tokens(
+            &json!({"foo": 123, ..&extra, ..None::<BTreeMap<&str, i32>>, ..{"baz": null}}),
+            &[
+                Token::Map { len: None },
+                Token::Str("foo"),
+                Token::I32(123),
+                Token::Str("bar"),
+                Token::I32(456),
+                Token::Str("baz"),
+                Token::None,
+                Token::MapEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn object_spread_serialized_once() {
+        use serde::Serialize;
+        use std::cell::Cell;
+
+        struct Counted<'a>(&'a Cell<usize>);
+
+        impl Serialize for Counted<'_> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                self.0.set(self.0.get() + 1);
+                BTreeMap::from([("bar", 456)]).serialize(serializer)
+            }
+        }
+
+        let calls = Cell::new(0);
+        let value = json!({"foo": 123, ..Counted(&calls)});
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#"{"foo":123,"bar":456}"#
+        );
+        assert_eq!(value.to_string(), r#"{"foo":123,"bar":456}"#);
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn object_spread_error() {
+        let err = serde_json::to_string(&json!({"foo": 123, ..vec![1, 2]})).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "can only spread a map or struct into an object, found a sequence"
+        );
+    }
+
+    #[test]
+    fn object_comprehension_ser() {
+        let scores = [("alice", 1), ("bob", 2)];
+        serde_test::assert_ser_tokens(
+            &json!({
+                "total": 3,
+                for (name, score) in scores => name: { "score": score },
+                "done": true,
+            }),
+            &[
+                Token::Map { len: Some(4) },
+                Token::Str("total"),
+                Token::I32(3),
+                Token::Str("alice"),
+                Token::Map { len: Some(1) },
+                Token::Str("score"),
+                Token::I32(1),
+                Token::MapEnd,
+                Token::Str("bob"),
+                Token::Map { len: Some(1) },
+                Token::Str("score"),
+                Token::I32(2),
+                Token::MapEnd,
+                Token::Str("done"),
+                Token::Bool(true),
+                Token::MapEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn object_comprehension_unknown_len() {
+        let names = ["a", "b", "c"];
+        serde_test::assert_ser_tokens(
+            &json!({ for x in names.iter().filter(|&&x| x != "b") => x: null }),
+            &[
+                Token::Map { len: None },
+                Token::Str("a"),
+                Token::None,
+                Token::Str("c"),
+                Token::None,
+                Token::MapEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn object_shorthand_ser() {
+        let name = "john";
+        let age = 43;
+        let r#type = "user";
+        let admin = true;
+        serde_test::assert_ser_tokens(
+            &json!({ name, "age": age, r#type, if admin => admin }),
+            &[
+                Token::Map { len: Some(4) },
+                Token::Str("name"),
+                Token::Str("john"),
+                Token::Str("age"),
+                Token::I32(43),
+                Token::Str("type"),
+                Token::Str("user"),
+                Token::Str("admin"),
+                Token::Bool(true),
+                Token::MapEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn object_rename_all_ser() {
+        let user_id = 1;
+        let _first_name = "john";
+        let last_name = "smith";
+        serde_test::assert_ser_tokens(
+            &json!({
+                #![rename_all = "camelCase"]
+                user_id,
+                _first_name,
+                "last_name": last_name,
+                "nested": { #![rename_all = "SCREAMING-KEBAB-CASE"] user_id },
+            }),
+            &[
+                Token::Map { len: Some(4) },
+                Token::Str("userId"),
+                Token::I32(1),
+                Token::Str("firstName"),
+                Token::Str("john"),
+                Token::Str("last_name"),
+                Token::Str("smith"),
+                Token::Str("nested"),
+                Token::Map { len: Some(1) },
+                Token::Str("USER-ID"),
+                Token::I32(1),
+                Token::MapEnd,
+                Token::MapEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn rename_all_cases() {
+        #[rustfmt::skip]
+        macro_rules! key {
+            ($case:tt) => {{
+                let new_user_id = ();
+                let value = json!({ #![rename_all = $case] new_user_id });
+                serde_json::to_string(&value).unwrap()
+            }};
+        }
+        assert_eq!(key!("lowercase"), r#"{"new_user_id":null}"#);
+        assert_eq!(key!("UPPERCASE"), r#"{"NEW_USER_ID":null}"#);
+        assert_eq!(key!("PascalCase"), r#"{"NewUserId":null}"#);
+        assert_eq!(key!("camelCase"), r#"{"newUserId":null}"#);
+        assert_eq!(key!("snake_case"), r#"{"new_user_id":null}"#);
+        assert_eq!(key!("SCREAMING_SNAKE_CASE"), r#"{"NEW_USER_ID":null}"#);
+        assert_eq!(key!("kebab-case"), r#"{"new-user-id":null}"#);
+        assert_eq!(key!("SCREAMING-KEBAB-CASE"), r#"{"NEW-USER-ID":null}"#);
+    }
+
+    #[test]
+    fn object_key_types_ser() {
+        let owned = String::from("owned");
+        let cow = std::borrow::Cow::Borrowed("cow");
+        serde_test::assert_ser_tokens(
+            &json!({ owned: 1, cow: 2, 'c': 3, 4u8: 4, f!("{}", 5): 5 }),
+            &[
+                Token::Map { len: Some(5) },
+                Token::Str("owned"),
+                Token::I32(1),
+                Token::Str("cow"),
+                Token::I32(2),
+                Token::Char('c'),
+                Token::I32(3),
+                Token::U8(4),
+                Token::I32(4),
+                Token::Str("5"),
+                Token::I32(5),
+                Token::MapEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn strict() {
+        let mut extra = BTreeMap::new();
+        extra.insert("bar", 2);
+        let value = crate::Strict(json!({ "foo": 1, ..&extra, "nested": [{ "a": 1, "b": 2 }] }));
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#"{"foo":1,"bar":2,"nested":[{"a":1,"b":2}]}"#
+        );
+    }
+
+    #[test]
+    fn strict_duplicate_keys() {
+        let mut extra = BTreeMap::new();
+        extra.insert("foo", 2);
+        let value = crate::Strict(json!({ "foo": 1, ..&extra }));
+        let err = serde_json::to_string(&value).unwrap_err();
+        assert_eq!(err.to_string(), "duplicate key `foo` in object");
+
+        let ids = [1, 2, 1];
+        let value = crate::Strict(json!([{ for id in ids => id: null }]));
+        let err = serde_json::to_string(&value).unwrap_err();
+        assert_eq!(err.to_string(), "duplicate key `1` in object");
+    }
+
     #[test]
     fn array() {
         let data = json!([123, 456]);
@@ -161,6 +529,1082 @@ mod tests {
         );
     }
 
+    #[test]
+    fn array_conditional() {
+        let yes = true;
+        let data = json!([if yes => 123, if !yes => 0, if yes { 456 } else { 0 }]);
+        let y = <Vec<i32>>::deserialize(data).unwrap();
+        assert_eq!(y, [123, 456]);
+    }
+
+    #[test]
+    fn array_conditional_ser() {
+        let yes = true;
+        serde_test::assert_ser_tokens(
+            &json!([if !yes => null, if yes => {"foo": 123}, if yes => yes]),
+            &[
+                Token::Seq { len: Some(2) },
+                Token::Map { len: Some(1) },
+                Token::Str("foo"),
+                Token::I32(123),
+                Token::MapEnd,
+                Token::Bool(true),
+                Token::SeqEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn array_spread_ser() {
+        let items = vec![2, 3];
+        serde_test::assert_ser_tokens(
+            &json!([1, ..&items, ..4..6]),
+            &[
+                Token::Seq { len: Some(5) },
+                Token::I32(1),
+                Token::I32(2),
+                Token::I32(3),
+                Token::I32(4),
+                Token::I32(5),
+                Token::SeqEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn array_spread_unknown_len() {
+        let items = [1, 2, 3];
+        serde_test::assert_ser_tokens(
+            &json!([..items.iter().filter(|&&x| x != 2), 4]),
+            &[
+                Token::Seq { len: None },
+                Token::I32(1),
+                Token::I32(3),
+                Token::I32(4),
+                Token::SeqEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn array_spread_cloned_once() {
+        use std::cell::Cell;
+
+        struct Counted<'a>(&'a Cell<usize>);
+        impl Clone for Counted<'_> {
+            fn clone(&self) -> Self {
+                self.0.set(self.0.get() + 1);
+                Counted(self.0)
+            }
+        }
+        impl IntoIterator for Counted<'_> {
+            type Item = i32;
+            type IntoIter = std::ops::Range<i32>;
+            fn into_iter(self) -> Self::IntoIter {
+                1..3
+            }
+        }
+
+        let clones = Cell::new(0);
+        let data = json!([0, ..Counted(&clones)]);
+        serde_test::assert_ser_tokens(
+            &data,
+            &[
+                Token::Seq { len: Some(3) },
+                Token::I32(0),
+                Token::I32(1),
+                Token::I32(2),
+                Token::SeqEnd,
+            ],
+        );
+        assert_eq!(clones.get(), 1);
+        assert_eq!(data.to_string(), "[0,1,2]");
+        assert_eq!(clones.get(), 2);
+    }
+
+    #[test]
+    fn array_comprehension_ser() {
+        let prefix = "item";
+        serde_test::assert_ser_tokens(
+            &json!([for (i, x) in [10, 20].iter().enumerate() => {"id": i, prefix: [x]}]),
+            &[
+                Token::Seq { len: Some(2) },
+                Token::Map { len: Some(2) },
+                Token::Str("id"),
+                Token::U64(0),
+                Token::Str("item"),
+                Token::Seq { len: Some(1) },
+                Token::I32(10),
+                Token::SeqEnd,
+                Token::MapEnd,
+                Token::Map { len: Some(2) },
+                Token::Str("id"),
+                Token::U64(1),
+                Token::Str("item"),
+                Token::Seq { len: Some(1) },
+                Token::I32(20),
+                Token::SeqEnd,
+                Token::MapEnd,
+                Token::SeqEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn array_comprehension_unknown_len() {
+        serde_test::assert_ser_tokens(
+            &json!([for x in (0..4).filter(|x| x % 2 == 0) => x]),
+            &[
+                Token::Seq { len: None },
+                Token::I32(0),
+                Token::I32(2),
+                Token::SeqEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn format_ser() {
+        let name = "john";
+        let shard = 4;
+        serde_test::assert_ser_tokens(
+            &json!({
+                f!("shard-{}", shard): [f!("hello {name}"), format_args!("{}!", 1)],
+                "msg": f!("{}", name),
+            }),
+            &[
+                Token::Map { len: Some(2) },
+                Token::Str("shard-4"),
+                Token::Seq { len: Some(2) },
+                Token::Str("hello john"),
+                Token::Str("1!"),
+                Token::SeqEnd,
+                Token::Str("msg"),
+                Token::Str("john"),
+                Token::MapEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn raw() {
+        let data = json!({ "foo": raw!("123") });
+        let x = Something::deserialize(data).unwrap();
+        assert_eq!(x.foo, 123);
+    }
+
+    #[test]
+    fn raw_ser() {
+        let body = String::from(r#"[1, {"a": null}]"#);
+        let body = crate::Raw::new(&body).unwrap();
+        let value = json!({ "body": body, "extra": [raw!("true"), raw!(concat!("{", "}"))] });
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#"{"body":[1, {"a": null}],"extra":[true,{}]}"#
+        );
+        assert_eq!(
+            value.to_string(),
+            r#"{"body":[1, {"a": null}],"extra":[true,{}]}"#
+        );
+        assert!(crate::Raw::new("[1, 2").is_err());
+        assert!(crate::Raw::new("1 2").is_err());
+    }
+
+    #[test]
+    fn raw_parsed_ser() {
+        const DOC: crate::ConstJson = crate::ConstJson::new_unchecked(
+            r#"{"a":[1,-2,0.5,true,null],"b\n":"\u00e9\ud83d\ude00\/","c":{}}"#,
+            "",
+        );
+        serde_test::assert_ser_tokens(
+            &DOC.parsed(),
+            &[
+                Token::Map { len: Some(3) },
+                Token::Str("a"),
+                Token::Seq { len: Some(5) },
+                Token::U64(1),
+                Token::I64(-2),
+                Token::F64(0.5),
+                Token::Bool(true),
+                Token::Unit,
+                Token::SeqEnd,
+                Token::Str("b\n"),
+                Token::Str("é😀/"),
+                Token::Str("c"),
+                Token::Map { len: Some(0) },
+                Token::MapEnd,
+                Token::MapEnd,
+            ],
+        );
+        assert_eq!(
+            serde_json::to_string(&DOC.parsed()).unwrap(),
+            r#"{"a":[1,-2,0.5,true,null],"b\n":"é😀/","c":{}}"#
+        );
+        assert!(serde_json::to_string(&crate::Raw::new_unchecked("[1,").parsed()).is_err());
+    }
+
+    #[test]
+    fn raw_is_valid() {
+        use crate::__private::is_valid;
+
+        for json in [
+            "null",
+            " true ",
+            "-0.5e+10",
+            r#"{"a": [1, {}, "\u00e9\n"], "b": false}"#,
+            "[]",
+        ] {
+            assert!(is_valid(json), "{}", json);
+        }
+        for json in [
+            "",
+            "{",
+            "[1,]",
+            r#"{"a" 1}"#,
+            "01",
+            "1.",
+            "\"\\x\"",
+            "\"\n\"",
+            "nul",
+            "1 2",
+        ] {
+            assert!(!is_valid(json), "{}", json);
+        }
+    }
+
+    #[test]
+    fn rendered_key_ser() {
+        use crate::__private::{Array, Expr, Map, Rendered, KV};
+
+        // the rendered keys are deliberately different, to see which one is written
+        let value = Map((
+            Some(KV::Rendered(
+                Rendered::new(r#""fast":"#),
+                Expr("slow"),
+                Array(Some(Map(Some(KV::Rendered(
+                    Rendered::new(r#""b":"#),
+                    Expr("a"),
+                    Expr(1),
+                ))))),
+            )),
+            Some(KV::Rendered(
+                Rendered::new("junk"),
+                Expr("plain"),
+                crate::Null,
+            )),
+        ));
+        assert_eq!(value.to_string(), r#"{"fast":[{"b":1}],"plain":null}"#);
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#"{"slow":[{"a":1}],"plain":null}"#
+        );
+        assert_eq!(
+            value.display().to_string(),
+            r#"{"slow":[{"a":1}],"plain":null}"#
+        );
+        assert_eq!(
+            format!("{:#.1}", value),
+            "{\n  \"slow\": [{\"a\":1}],\n  \"plain\": null\n}"
+        );
+        serde_test::assert_ser_tokens(
+            &value,
+            &[
+                Token::Map { len: Some(2) },
+                Token::Str("slow"),
+                Token::Seq { len: Some(1) },
+                Token::Map { len: Some(1) },
+                Token::Str("a"),
+                Token::I32(1),
+                Token::MapEnd,
+                Token::SeqEnd,
+                Token::Str("plain"),
+                Token::None,
+                Token::MapEnd,
+            ],
+        );
+        assert_eq!(
+            serde_json::Value::deserialize(value).unwrap(),
+            serde_json::json!({ "slow": [{ "a": 1 }], "plain": null })
+        );
+
+        // only a string literal that is also a JSON string is kept
+        for key in [r#""key é":"#, r#""":"#, r#""a\"b":"#, r#""a\nb":"#] {
+            assert_eq!(Rendered::new(key).get(), Some(key));
+        }
+        for key in [
+            "\"a\nb\":",
+            r#""a\'b":"#,
+            r#""\u{e9}":"#,
+            r#""a"b":"#,
+            r#""a""#,
+            r#"r"a":"#,
+            r#"b"a":"#,
+            "'a':",
+            "1:",
+        ] {
+            assert_eq!(Rendered::new(key).get(), None, "{}", key);
+        }
+    }
+
+    #[test]
+    fn writer_ser() {
+        use serde::Serialize;
+
+        #[derive(Serialize)]
+        enum Shape {
+            Unit,
+            Newtype(u8),
+            Tuple(i8, f32),
+            Struct { a: (), b: Option<char> },
+        }
+
+        #[derive(Serialize)]
+        struct Wrapper<'a>(&'a str);
+
+        struct Bytes(&'static [u8]);
+
+        impl Serialize for Bytes {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(self.0)
+            }
+        }
+
+        /// A map with keys that `json!` would reject.
+        struct Entries<K, V>(Vec<(K, V)>);
+
+        impl<K: Serialize, V: Serialize> Serialize for Entries<K, V> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_map(self.0.iter().map(|(k, v)| (k, v)))
+            }
+        }
+
+        fn check(value: &(impl Serialize + ?Sized)) {
+            let mut compact = String::new();
+            crate::to_writer(&mut compact, value).unwrap();
+            assert_eq!(compact, serde_json::to_string(value).unwrap());
+
+            let mut pretty = String::new();
+            crate::to_writer_pretty(&mut pretty, value).unwrap();
+            assert_eq!(pretty, serde_json::to_string_pretty(value).unwrap());
+        }
+
+        let text = "quote \" slash \\ \n\r\t \u{8}\u{c} \u{0}\u{1f} \u{7f} é 😀 </script>";
+        let mut keys = BTreeMap::new();
+        keys.insert(-1i64, [Shape::Unit, Shape::Newtype(7)]);
+        keys.insert(
+            2,
+            [
+                Shape::Tuple(-3, 0.5),
+                Shape::Struct {
+                    a: (),
+                    b: Some('"'),
+                },
+            ],
+        );
+
+        check(&json!({
+            "text": text,
+            text: [text, 'x', Wrapper(text)],
+            "numbers": [0, -1i8, u64::MAX, i128::MIN, u128::MAX, 1.5, -0.0, 1e-7, 0.1f32],
+            "large": [1e16, 1e20, 1.2345678901234568e20, 1e300, -1e21f32, f64::MAX, 1e15],
+            "floats": [f64::NAN, f64::INFINITY, f32::NEG_INFINITY],
+            "empty": [{}, [], "", Vec::<u8>::new(), BTreeMap::<u8, u8>::new()],
+            "keys": keys,
+            "keys2": [Entries(vec![(true, 'c')]), Entries(vec![(1.5, 2.5f32), (1e20, 1e-20)])],
+            "bytes": Bytes(b"\x00\xff"),
+            "raw": raw!(r#"{"already": ["json"]}"#),
+            "format": f!("{}\n{:?}", text, text),
+            "nothing": [null, None::<u8>, (), Some(1)],
+        }));
+        check(&[Shape::Struct { a: (), b: None }]);
+        check(&json!([]));
+        check(&1.0f64);
+        check(&[1e300, 1.5e-300]);
+
+        let mut out = String::new();
+
+        assert_eq!(
+            crate::to_writer(&mut out, &Entries(vec![([1], 2)]))
+                .unwrap_err()
+                .to_string(),
+            "key must be a string"
+        );
+        assert_eq!(
+            crate::to_writer(&mut out, &Entries(vec![(f64::NAN, 1)]))
+                .unwrap_err()
+                .to_string(),
+            "float key must be finite (got NaN or +/-inf)"
+        );
+    }
+
+    #[test]
+    fn pretty_options() {
+        use crate::{Formatter, Pretty, Serializer};
+        use serde::Serialize;
+
+        let value = json!({ "a": [1, { "b": [] }], "c": {} });
+
+        assert_eq!(
+            format!("{:#}", value),
+            serde_json::to_string_pretty(&value).unwrap()
+        );
+        assert_eq!(
+            format!("{:#3}", value),
+            "{\n   \"a\": [\n      1,\n      {\n         \"b\": []\n      }\n   ],\n   \"c\": {}\n}"
+        );
+        assert_eq!(
+            format!("{:-<#}", value),
+            "{\n-\"a\": [\n--1,\n--{\n---\"b\": []\n--}\n-],\n-\"c\": {}\n}"
+        );
+        assert_eq!(format!("{:#.0}", value), value.to_string());
+        assert_eq!(
+            format!("{:#.2}", value),
+            "{\n  \"a\": [\n    1,\n    {\"b\":[]}\n  ],\n  \"c\": {}\n}"
+        );
+        // not alternate, so compact
+        assert_eq!(format!("{:4.1}", value), value.to_string());
+
+        let pretty = Pretty::new()
+            .indent("    ")
+            .newline("\r\n")
+            .separator(" : ");
+        assert_eq!(
+            pretty.display(&value).to_string(),
+            "{\r\n    \"a\" : [\r\n        1,\r\n        {\r\n            \"b\" : []\r\n        }\r\n    ],\r\n    \"c\" : {}\r\n}"
+        );
+        assert_eq!(
+            pretty.depth(1).display(&value).to_string(),
+            "{\r\n    \"a\" : [1,{\"b\":[]}],\r\n    \"c\" : {}\r\n}"
+        );
+
+        // the display doesn't change the layout it was given
+        let display = Pretty::new().depth(1).display(&value);
+        assert_eq!(display.to_string(), display.to_string());
+
+        fn write(value: &impl Serialize, formatter: impl Formatter) -> String {
+            let mut ser = Serializer::with_formatter(String::new(), formatter);
+            value.serialize(&mut ser).unwrap();
+            ser.into_inner()
+        }
+        assert_eq!(
+            write(&json!([[1], 2]), Pretty::new().indent("\t").depth(1)),
+            "[\n\t[1],\n\t2\n]"
+        );
+    }
+
+    #[test]
+    fn width_ser() {
+        use crate::Pretty;
+        use serde::Serialize;
+
+        #[derive(Serialize)]
+        enum Event {
+            Codes(Vec<u16>),
+        }
+
+        let value = json!({
+            "result": { "error": [503, "failed"] },
+            "codes": [[503], 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+            "é": Event::Codes(vec![1, 2]),
+        });
+        let width = |width| Pretty::new().width(width).display(&value).to_string();
+
+        assert_eq!(width(200), value.to_string());
+        assert_eq!(
+            width(40),
+            r#"{
+  "result": {"error":[503,"failed"]},
+  "codes": [
+    [503],
+    2,
+    3,
+    4,
+    5,
+    6,
+    7,
+    8,
+    9,
+    10,
+    11,
+    12,
+    13,
+    14,
+    15
+  ],
+  "é": {"Codes":[1,2]}
+}"#
+        );
+        // `  "result": {"error":[503,"failed"]},` is exactly 37 columns, and `é` is one
+        assert_eq!(width(37), width(40));
+        assert_eq!(
+            width(36),
+            r#"{
+  "result": {
+    "error": [503,"failed"]
+  },
+  "codes": [
+    [503],
+    2,
+    3,
+    4,
+    5,
+    6,
+    7,
+    8,
+    9,
+    10,
+    11,
+    12,
+    13,
+    14,
+    15
+  ],
+  "é": {"Codes":[1,2]}
+}"#
+        );
+        assert_eq!(width(0), serde_json::to_string_pretty(&value).unwrap());
+        assert_eq!(
+            Pretty::new().width(40).depth(1).display(&value).to_string(),
+            format!("{:#.1}", value)
+        );
+
+        let mut out = String::new();
+        crate::to_writer_width(&mut out, &json!([[1, 2], [3, 4]]), 8).unwrap();
+        assert_eq!(out, "[\n  [1,2],\n  [3,4]\n]");
+    }
+
+    #[test]
+    #[allow(clippy::excessive_precision)] // the numbers are from RFC 8785
+    fn canonical_ser() {
+        use crate::to_canonical_string;
+        use serde::Serialize;
+
+        #[derive(Serialize)]
+        enum Shape {
+            Unit,
+            Newtype(u8),
+            Tuple(i8, f32),
+            Struct { b: (), a: Option<char> },
+        }
+
+        // the examples from RFC 8785, sections 3.2.2 and 3.2.3
+        let value = json!({
+            "numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+            "string": "\u{20ac}$\u{f}\nA'\u{42}\"\\\\\"/",
+            "literals": [null, true, false],
+        });
+        assert_eq!(
+            to_canonical_string(&value).unwrap(),
+            r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#
+        );
+        let value = json!({
+            "\u{20ac}": "Euro Sign",
+            "\r": "Carriage Return",
+            "\u{fb33}": "Hebrew Letter Dalet With Dagesh",
+            "1": "One",
+            "\u{1f600}": "Emoji: Grinning Face",
+            "\u{80}": "Control",
+            "\u{f6}": "Latin Small Letter O With Diaeresis",
+        });
+        let keys = ["\\r", "1", "\u{80}", "ö", "€", "😀", "\u{fb33}"];
+        let canonical = to_canonical_string(&value).unwrap();
+        let found: Vec<usize> = keys.iter().map(|k| canonical.find(k).unwrap()).collect();
+        assert!(found.windows(2).all(|w| w[0] < w[1]), "{}", canonical);
+
+        // I-JSON has no duplicate keys, compared by the string they are written as
+        let (key, one) = ("a", 1);
+        let err = to_canonical_string(&json!({ "a": 1, "b": 2, key: 3 })).unwrap_err();
+        assert_eq!(err.to_string(), "duplicate key `a` in object");
+        let err = to_canonical_string(&json!({ "x": [{ "1": 1, one: 2 }] })).unwrap_err();
+        assert_eq!(err.to_string(), "duplicate key `1` in object");
+        assert_eq!(
+            to_canonical_string(&json!({ 'c': 1, -2i8: 2, one: 3 })).unwrap(),
+            r#"{"-2":2,"1":3,"c":1}"#
+        );
+
+        // ECMAScript's Number.prototype.toString
+        let numbers = [
+            (0.0, "0"),
+            (-0.0, "0"),
+            (1.0, "1"),
+            (-1.5, "-1.5"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (123e18, "123000000000000000000"),
+            (1e21, "1e+21"),
+            (9.999999999999997e22, "9.999999999999997e+22"),
+            (1e23, "1e+23"),
+            (1e-6, "0.000001"),
+            (1.5e-6, "0.0000015"),
+            (1e-7, "1e-7"),
+            (1.25e-7, "1.25e-7"),
+            (5e-324, "5e-324"),
+            (f64::MAX, "1.7976931348623157e+308"),
+            (295147905179352830000.0, "295147905179352830000"),
+        ];
+        for (number, expected) in numbers {
+            assert_eq!(to_canonical_string(&number).unwrap(), expected);
+        }
+        assert_eq!(
+            to_canonical_string(&(1u64 << 53)).unwrap(),
+            "9007199254740992"
+        );
+        assert_eq!(
+            to_canonical_string(&u64::MAX).unwrap(),
+            "18446744073709552000"
+        );
+        assert_eq!(
+            to_canonical_string(&i128::MIN).unwrap(),
+            "-1.7014118346046923e+38"
+        );
+        assert_eq!(to_canonical_string(&0.1f32).unwrap(), "0.10000000149011612");
+        assert_eq!(
+            to_canonical_string(&f64::NAN).unwrap_err().to_string(),
+            "number must be finite (got NaN or +/-inf)"
+        );
+
+        let mut map = BTreeMap::new();
+        map.insert(
+            10,
+            Shape::Struct {
+                b: (),
+                a: Some('x'),
+            },
+        );
+        map.insert(9, Shape::Tuple(-1, 0.5));
+        let value = json!([
+            Shape::Unit,
+            Shape::Newtype(1),
+            map,
+            crate::Raw::new(r#"{ "z": 1.0, "a": [1E2] }"#).unwrap(),
+            f!("{}", "\u{7f}\u{2028}"),
+            b"ab",
+            {},
+            [],
+        ]);
+        assert_eq!(
+            to_canonical_string(&value).unwrap(),
+            "[\"Unit\",{\"Newtype\":1},{\"10\":{\"Struct\":{\"a\":\"x\",\"b\":null}},\"9\":{\"Tuple\":[-1,0.5]}},{\"a\":[100],\"z\":1},\"\u{7f}\u{2028}\",[97,98],{},[]]"
+        );
+        assert!(to_canonical_string(&crate::Raw::new_unchecked("{")).is_err());
+    }
+
+    #[test]
+    fn escape_ser() {
+        use crate::{Pretty, Raw, Serializer};
+        use serde::Serialize;
+
+        let text = "<a href=\"?x&y\">\u{2028}\u{2029}é🦀\n\u{1}";
+        let key = String::from("<é>");
+        let raw = Raw::new(r#"{"<\"":"é\u2028"}"#).unwrap();
+        let value = json!({ "text": text, key: ['&', '~', 'ü'], "raw": raw });
+
+        assert_eq!(
+            value.display().to_string(),
+            serde_json::to_string(&value).unwrap()
+        );
+        assert_eq!(
+            value.display().html_safe().to_string(),
+            r#"{"text":"\u003ca href=\"?x\u0026y\"\u003e\u2028\u2029é🦀\n\u0001","\u003cé\u003e":["\u0026","~","ü"],"raw":{"\u003c\"":"é\u2028"}}"#
+        );
+        assert_eq!(
+            value.display().ascii_only().to_string(),
+            r#"{"text":"<a href=\"?x&y\">\u2028\u2029\u00e9\ud83e\udd80\n\u0001","<\u00e9>":["&","~","\u00fc"],"raw":{"<\"":"\u00e9\u2028"}}"#
+        );
+        assert_eq!(
+            value
+                .display()
+                .pretty()
+                .html_safe()
+                .ascii_only()
+                .to_string(),
+            r#"{
+  "text": "\u003ca href=\"?x\u0026y\"\u003e\u2028\u2029\u00e9\ud83e\udd80\n\u0001",
+  "\u003c\u00e9\u003e": [
+    "\u0026",
+    "~",
+    "\u00fc"
+  ],
+  "raw": {"\u003c\"":"\u00e9\u2028"}
+}"#
+        );
+
+        // constant parts rendered by the macro, and `collect_str`
+        let value = json!(["</script>", "é", format_args!("{}<{}", 1, 'é')]);
+        assert_eq!(
+            value.display().html_safe().ascii_only().to_string(),
+            r#"["\u003c/script\u003e","\u00e9","1\u003c\u00e9"]"#
+        );
+        let mut ser = Serializer::pretty(String::new()).html_safe();
+        value.serialize(&mut ser).unwrap();
+        assert_eq!(
+            ser.into_inner(),
+            "[\n  \"\\u003c/script\\u003e\",\n  \"é\",\n  \"1\\u003cé\"\n]"
+        );
+
+        // the width is measured with the escapes
+        let value = json!({ "tag": ["<<<<", "<<<<"] });
+        let width = Pretty::new().width(40).display(&value);
+        assert_eq!(width.to_string(), r#"{"tag":["<<<<","<<<<"]}"#);
+        assert_eq!(
+            width.html_safe().to_string(),
+            r#"{
+  "tag": [
+    "\u003c\u003c\u003c\u003c",
+    "\u003c\u003c\u003c\u003c"
+  ]
+}"#
+        );
+    }
+
+    #[test]
+    fn branch_ser() {
+        fn value(ok: bool, code: u32) -> impl serde::Serialize + core::fmt::Display + Clone {
+            json!({
+                "result": if ok { { "value": code } } else { { "error": [code, "failed"] } },
+                "kind": if code < 300 { "success" } else if code < 500 { "client" } else { null },
+                "codes": [
+                    match code {
+                        200 => "ok",
+                        404 => { "missing": true },
+                        c if c >= 500 => [c],
+                        _ => null
+                    },
+                    if ok { 1 } else { 2 }
+                ],
+                if ok { "yes" } else { "no" }: match code { c => c },
+            })
+        }
+
+        assert_eq!(
+            serde_json::to_string(&value(true, 200)).unwrap(),
+            r#"{"result":{"value":200},"kind":"success","codes":["ok",1],"yes":200}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&value(false, 404)).unwrap(),
+            r#"{"result":{"error":[404,"failed"]},"kind":"client","codes":[{"missing":true},2],"no":404}"#
+        );
+        assert_eq!(
+            value(false, 503).to_string(),
+            r#"{"result":{"error":[503,"failed"]},"kind":null,"codes":[[503],2],"no":503}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&json!(match 1 {
+                _ => "only",
+            }))
+            .unwrap(),
+            r#""only""#
+        );
+
+        let data = json!({ "foo": if true { 123 } else { 456 }, "bar"?: None::<i32>, "baz": match 1 { 0 => null, _ => 789 } });
+        let x = Optional::deserialize(data).unwrap();
+        assert_eq!((x.foo, x.bar, x.baz), (123, None, Some(789)));
+        serde_test::assert_ser_tokens(
+            &json!([
+                if false { 1 } else { "two" },
+                match 'c' {
+                    'a' => 1,
+                    'b' => 2,
+                    _ => 3u8,
+                }
+            ]),
+            &[
+                Token::Seq { len: Some(2) },
+                Token::Str("two"),
+                Token::U8(3),
+                Token::SeqEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn cfg_ser() {
+        let (id, extra) = (1, [("team", 2)]);
+        let extra: BTreeMap<_, _> = extra.into_iter().collect();
+
+        let data = json!({
+            "id": id,
+            #[cfg(test)]
+            "debug": true,
+            #[cfg(not(test))]
+            "debug": missing_in_tests(),
+            #[cfg(any())]
+            unknown,
+            #[cfg(test)] #[allow(unused_parens)]
+            if id > 0 => "positive": (id > 0),
+            #[cfg(not(test))]
+            ..&extra,
+            #[cfg(test)]
+            for (key, value) in &extra => key: value,
+        });
+
+        assert_eq!(
+            serde_json::to_string(&data).unwrap(),
+            r#"{"id":1,"debug":true,"positive":true,"team":2}"#
+        );
+        serde_test::assert_ser_tokens(
+            &data,
+            &[
+                Token::Map { len: Some(4) },
+                Token::Str("id"),
+                Token::I32(1),
+                Token::Str("debug"),
+                Token::Bool(true),
+                Token::Str("positive"),
+                Token::Bool(true),
+                Token::Str("team"),
+                Token::I32(2),
+                Token::MapEnd,
+            ],
+        );
+        serde_test::assert_ser_tokens(
+            &json!({ "id": id, #[cfg(any())] "name": unknown }),
+            &[
+                Token::Map { len: Some(1) },
+                Token::Str("id"),
+                Token::I32(1),
+                Token::MapEnd,
+            ],
+        );
+
+        let data = json!([
+            1,
+            #[cfg(test)]
+            2,
+            #[cfg(not(test))]
+            missing_in_tests(),
+            #[cfg(any())] unknown,
+            #[cfg(test)] #[allow(unused_parens)]
+            if id > 0 => (id > 0),
+            #[cfg(test)]
+            ..&extra,
+            #[cfg(test)]
+            { "id": id }
+        ]);
+        assert_eq!(
+            serde_json::to_string(&data).unwrap(),
+            r#"[1,2,true,["team",2],{"id":1}]"#
+        );
+        assert_eq!(data.to_string(), r#"[1,2,true,["team",2],{"id":1}]"#);
+        serde_test::assert_ser_tokens(
+            &json!([
+                1,
+                #[cfg(any())]
+                unknown,
+                3
+            ]),
+            &[
+                Token::Seq { len: Some(2) },
+                Token::I32(1),
+                Token::I32(3),
+                Token::SeqEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn builder_ser() {
+        use crate::{Array, Map};
+
+        let (name, nickname, admin) = ("John Doe", None::<&str>, false);
+        let built = Map::new()
+            .entry("name", name)
+            .optional("nickname", nickname)
+            .entry(
+                "groups",
+                Array::new().push("users").push_if(admin, "admins").push(1),
+            )
+            .entry("meta", Map::new());
+        let expected = json!({
+            "name": name,
+            "nickname"?: nickname,
+            "groups": ["users", if admin => "admins", 1],
+            "meta": {},
+        });
+
+        assert_eq!(
+            serde_json::to_string(&built).unwrap(),
+            serde_json::to_string(&expected).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_string_pretty(&built).unwrap(),
+            serde_json::to_string_pretty(&expected).unwrap()
+        );
+        assert_eq!(built.to_string(), expected.to_string());
+        assert_eq!(
+            serde_json::Value::deserialize(built).unwrap(),
+            serde_json::Value::deserialize(expected).unwrap()
+        );
+        let tokens = [
+            Token::Map { len: Some(3) },
+            Token::Str("name"),
+            Token::Str("John Doe"),
+            Token::Str("groups"),
+            Token::Seq { len: Some(2) },
+            Token::Str("users"),
+            Token::I32(1),
+            Token::SeqEnd,
+            Token::Str("meta"),
+            Token::Map { len: Some(0) },
+            Token::MapEnd,
+            Token::MapEnd,
+        ];
+        serde_test::assert_ser_tokens(&built, &tokens);
+        serde_test::assert_ser_tokens(&expected, &tokens);
+
+        // every optional entry and conditional element, present or left out, at any depth
+        for (nickname, admin) in [
+            (None, false),
+            (Some("Johnny"), false),
+            (None, true),
+            (Some("J"), true),
+        ] {
+            let built = Map::new()
+                .optional("nickname", nickname)
+                .entry("id", 7)
+                .entry(
+                    "groups",
+                    Array::new()
+                        .push_if(admin, "admins")
+                        .push(Map::new().optional("nickname", nickname))
+                        .push(Array::new().push_if(!admin, Array::new().push(Map::new()))),
+                )
+                .optional("admin", admin.then(|| Map::new().entry("since", 2020)))
+                .optional("alias", nickname);
+            let expected = json!({
+                "nickname"?: nickname,
+                "id": 7,
+                "groups": [
+                    if admin => "admins",
+                    { "nickname"?: nickname },
+                    [if !admin => [{}]],
+                ],
+                "admin"?: admin.then(|| json!({ "since": 2020 })),
+                "alias"?: nickname,
+            });
+
+            assert_eq!(built.to_string(), expected.to_string());
+            assert_eq!(format!("{:#}", built), format!("{:#}", expected));
+            assert_eq!(
+                serde_json::to_string(&built).unwrap(),
+                serde_json::to_string(&expected).unwrap()
+            );
+            assert_eq!(
+                serde_json::to_string_pretty(&built).unwrap(),
+                serde_json::to_string_pretty(&expected).unwrap()
+            );
+        }
+
+        // the builders nest to the left, unlike the balanced `json!` types
+        let built: Map<((((), _), _), _)> = Map::new().entry("a", 1).entry("b", 2).entry("c", 3);
+        let expected: Map<(_, (_, _))> = json!({ "a": 1, "b": 2, "c": 3 });
+        assert_eq!(built.to_string(), expected.to_string());
+        assert_eq!(
+            serde_json::to_string(&built).unwrap(),
+            serde_json::to_string(&expected).unwrap()
+        );
+        let built: Array<((((), _), _), _)> = Array::new().push(1).push(2).push(3);
+        let expected: Array<(_, (_, _))> = json!([1, 2, 3]);
+        assert_eq!(built.to_string(), expected.to_string());
+        assert_eq!(
+            serde_json::to_string(&built).unwrap(),
+            serde_json::to_string(&expected).unwrap()
+        );
+    }
+
+    #[cfg(feature = "macros")]
+    #[test]
+    fn json_type() {
+        trait Document {
+            type Body: serde::Serialize;
+            fn body(&self) -> Self::Body;
+        }
+
+        struct User<'a> {
+            id: &'a str,
+            name: &'a str,
+            email: Option<&'a str>,
+            roles: [&'a str; 2],
+            groups: &'a [&'a str],
+            labels: &'a BTreeMap<&'a str, i32>,
+        }
+
+        impl<'a> Document for User<'a> {
+            type Body = json_type!({
+                "kind": "user",
+                (&'a str): &'a str,
+                "email"?: &'a str,
+                "roles": [&'a str; 2],
+                "groups": ["everyone", ..core::slice::Iter<'a, &'a str>],
+                "meta": { "version": 1, "admin": bool, "raw": raw!("[]"), "deleted": null },
+                ..&'a BTreeMap<&'a str, i32>
+            });
+
+            fn body(&self) -> Self::Body {
+                json!({
+                    "kind": "user",
+                    self.id: self.name,
+                    "email"?: self.email,
+                    "roles": [self.roles[0], self.roles[1]],
+                    "groups": ["everyone", ..self.groups.iter()],
+                    "meta": {
+                        "version": 1,
+                        "admin": self.roles.contains(&"admin"),
+                        "raw": raw!("[]"),
+                        "deleted": null
+                    },
+                    ..self.labels
+                })
+            }
+        }
+
+        let mut labels = BTreeMap::new();
+        labels.insert("team", 1);
+        let user = User {
+            id: "john",
+            name: "John Doe",
+            email: None,
+            roles: ["admin", "user"],
+            groups: &["staff"],
+            labels: &labels,
+        };
+        assert_eq!(
+            serde_json::to_string(&user.body()).unwrap(),
+            r#"{"kind":"user","john":"John Doe","roles":["admin","user"],"groups":["everyone","staff"],"meta":{"version":1,"admin":true,"raw":[],"deleted":null},"team":1}"#
+        );
+
+        const NAME: &str = "status";
+        static EMPTY: json_type!([{}, [], ..core::iter::Empty<u8>]) =
+            json!([{}, [], ..core::iter::empty()]);
+        static STATUS: json_type!({ "ok": true, "errors": [], "count": -1i64, (&str): char }) =
+            json!({ "ok": true, "errors": [], "count": -1i64, NAME: 'x' });
+        assert_eq!(serde_json::to_string(&EMPTY).unwrap(), r#"[{},[]]"#);
+        assert_eq!(
+            serde_json::to_string(&STATUS).unwrap(),
+            r#"{"ok":true,"errors":[],"count":-1,"status":"x"}"#
+        );
+
+        const ID: u32 = 7;
+        static TRACED: json_type!({
+            "id": u32,
+            #[cfg(test)]
+            "test": bool,
+            #[cfg(not(test))] #[cfg(unix)]
+            "release": bool,
+            "stages": [&str, #[cfg(test)] &str, #[cfg(not(test))] u32],
+        }) = json!({
+            "id": ID,
+            #[cfg(test)]
+            "test": true,
+            #[cfg(not(test))] #[cfg(unix)]
+            "release": true,
+            "stages": ["build", #[cfg(test)] "test", #[cfg(not(test))] ID],
+        });
+        assert_eq!(
+            serde_json::to_string(&TRACED).unwrap(),
+            r#"{"id":7,"test":true,"stages":["build","test"]}"#
+        );
+    }
+
     #[test]
     fn complex_ser() {
         let value1 = 123;
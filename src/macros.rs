@@ -19,9 +19,10 @@
 /// Variables or expressions can be interpolated into the JSON literal. Any type
 /// interpolated into an array element or object value must implement Serde's
 /// `Serialize` trait, while any type interpolated into a object key must
-/// implement `Into<String>`. If the `Serialize` implementation of the
-/// interpolated type decides to fail, or if the interpolated type contains a
-/// map with non-string keys, the `json!` macro will panic.
+/// implement [`JsonKey`](crate::JsonKey), so keys are always valid JSON keys.
+/// If the `Serialize` implementation of the interpolated type decides to fail,
+/// or if the interpolated type contains a map with non-string keys,
+/// serializing the value will return an error.
 ///
 /// ```
 /// # use typed_json::json;
@@ -38,6 +39,289 @@
 /// });
 /// ```
 ///
+/// An object entry written with `?:` takes an `Option` and is left out of
+/// the object entirely when the value is `None`, rather than being `null`.
+///
+/// ```
+/// # use typed_json::json;
+/// #
+/// let nickname: Option<&str> = None;
+/// let email = Some("john@example.com");
+///
+/// let value = json!({
+///     "name": "John Doe",
+///     "nickname"?: nickname,
+///     "email"?: email,
+/// });
+///
+/// assert_eq!(
+///     serde_json::to_string(&value).unwrap(),
+///     r#"{"name":"John Doe","email":"john@example.com"}"#,
+/// );
+/// ```
+///
+/// Object entries and array elements can be included based on a condition
+/// that is checked at runtime, with `if cond => "key": value` and
+/// `if cond => value` respectively.
+///
+/// ```
+/// # use typed_json::json;
+/// #
+/// let admin = false;
+///
+/// let value = json!({
+///     "name": "John Doe",
+///     if admin => "permissions": ["read", "write"],
+///     "groups": ["users", if admin => "admins"],
+/// });
+///
+/// assert_eq!(
+///     serde_json::to_string(&value).unwrap(),
+///     r#"{"name":"John Doe","groups":["users"]}"#,
+/// );
+/// ```
+///
+/// Object entries and array elements can have outer attributes. With
+/// `#[cfg(...)]` the entry or element is compiled in or out like any other
+/// item, and one that is compiled out is not part of the type or the length.
+///
+/// ```
+/// # use typed_json::json;
+/// #
+/// let trace_id = 42;
+///
+/// let value = json!({
+///     "status": "ok",
+///     #[cfg(debug_assertions)]
+///     "trace_id": trace_id,
+///     "checks": ["auth", #[cfg(debug_assertions)] "trace"],
+/// });
+///
+/// if cfg!(debug_assertions) {
+///     assert_eq!(
+///         value.to_string(),
+///         r#"{"status":"ok","trace_id":42,"checks":["auth","trace"]}"#,
+///     );
+/// } else {
+///     assert_eq!(value.to_string(), r#"{"status":"ok","checks":["auth"]}"#);
+/// }
+/// ```
+///
+/// The entries of another map can be spread into an object with `..value`.
+/// This works with anything that serializes as a map or struct, including
+/// another `json!` object, and the entries are written inline without
+/// collecting them first.
+///
+/// ```
+/// # use typed_json::json;
+/// # use std::collections::BTreeMap;
+/// #
+/// let mut labels = BTreeMap::new();
+/// labels.insert("team", "platform");
+///
+/// let value = json!({
+///     "id": 1,
+///     ..labels,
+///     ..{ "version": 2 },
+/// });
+///
+/// assert_eq!(
+///     serde_json::to_string(&value).unwrap(),
+///     r#"{"id":1,"team":"platform","version":2}"#,
+/// );
+/// ```
+///
+/// Similarly, the items of any cloneable `IntoIterator` can be spread into an
+/// array with `..iter`. The items are streamed straight into the array rather
+/// than being nested as another array. The iterator is cloned each time the
+/// array is serialized, so spread a collection by reference as `..&vec`. By
+/// value, `..vec` clones the whole collection every time, while a range or an
+/// iterator adapter is cheap to clone.
+///
+/// ```
+/// # use typed_json::json;
+/// #
+/// let events = vec!["created", "updated"];
+///
+/// // `..&events` rather than `..events`, so the `Vec` isn't cloned to be serialized
+/// let value = json!(["start", ..&events, ..(1..=2), "end"]);
+///
+/// assert_eq!(
+///     serde_json::to_string(&value).unwrap(),
+///     r#"["start","created","updated",1,2,"end"]"#,
+/// );
+/// ```
+///
+/// A value can be chosen at runtime with `if cond { value } else { value }`
+/// or `match expr { pat => value, ... }`, where each branch is a JSON
+/// literal of its own. The branches can have different types, so the value
+/// is an [`Either`](crate::Either) of them. A Rust `if` or `match` whose
+/// blocks hold statements can still be interpolated by wrapping it in
+/// parentheses.
+///
+/// ```
+/// # use typed_json::json;
+/// #
+/// let status = 404;
+///
+/// let value = json!({
+///     "ok": if status < 400 { { "code": status } } else { null },
+///     "kind": match status {
+///         200 => "found",
+///         404 => ["missing", status],
+///         _ => { "unknown": status },
+///     },
+/// });
+///
+/// assert_eq!(
+///     serde_json::to_string(&value).unwrap(),
+///     r#"{"ok":null,"kind":["missing",404]}"#,
+/// );
+/// ```
+///
+/// An array can be built from an iterator with `[for pat in iter => value]`,
+/// where `value` is itself a JSON literal that can use the bindings from
+/// `pat`. See [`Comprehension`](crate::Comprehension) for more.
+///
+/// ```
+/// # use typed_json::json;
+/// #
+/// let ids = [1, 2, 3];
+///
+/// let value = json!({ "users": [for id in ids => { "id": id }] });
+///
+/// assert_eq!(
+///     serde_json::to_string(&value).unwrap(),
+///     r#"{"users":[{"id":1},{"id":2},{"id":3}]}"#,
+/// );
+/// ```
+///
+/// Objects can also contain entries produced from an iterator with
+/// `for pat in iter => key: value`, alongside the usual entries.
+///
+/// ```
+/// # use typed_json::json;
+/// #
+/// let scores = [("alice", 3), ("bob", 5)];
+///
+/// let value = json!({
+///     "game": "chess",
+///     for (name, score) in scores => name: { "score": score },
+/// });
+///
+/// assert_eq!(
+///     serde_json::to_string(&value).unwrap(),
+///     r#"{"game":"chess","alice":{"score":3},"bob":{"score":5}}"#,
+/// );
+/// ```
+///
+/// Strings can be formatted in place with `f!("...", args)`, which takes the
+/// same arguments as [`format!`]. `format_args!(...)` is accepted too. The
+/// string is written straight into the output while serializing, so unlike
+/// calling `format!` it does not allocate. This works for keys as well as
+/// values.
+///
+/// ```
+/// # use typed_json::json;
+/// #
+/// let user = "john";
+/// let shard = 3;
+///
+/// let value = json!({
+///     f!("shard-{}", shard): {
+///         "msg": f!("user {user} logged in"),
+///     }
+/// });
+///
+/// assert_eq!(
+///     serde_json::to_string(&value).unwrap(),
+///     r#"{"shard-3":{"msg":"user john logged in"}}"#,
+/// );
+/// ```
+///
+/// A variable on its own is shorthand for an entry named after it, so
+/// `{ name }` is the same as `{ "name": name }`. Starting an object with
+/// `#![rename_all = "..."]` converts the shorthand names in that object to
+/// another case at compile time. The supported cases are the same as serde's
+/// `rename_all`, and explicit keys are left alone.
+///
+/// ```
+/// # use typed_json::json;
+/// #
+/// let user_id = 42;
+/// let display_name = "John Doe";
+///
+/// let value = json!({
+///     #![rename_all = "camelCase"]
+///     user_id,
+///     display_name,
+///     "is_admin": false,
+/// });
+///
+/// assert_eq!(
+///     serde_json::to_string(&value).unwrap(),
+///     r#"{"userId":42,"displayName":"John Doe","is_admin":false}"#,
+/// );
+/// ```
+///
+/// With the `proc-macro` feature, `#![sort_keys]` sorts the entries of an
+/// object by their keys at compile time, in the same order as
+/// [`to_canonical_string`](crate::to_canonical_string). Every key in the
+/// object has to be a literal or a shorthand name.
+///
+/// ```
+/// # #[cfg(feature = "proc-macro")] {
+/// # use typed_json::json;
+/// #
+/// let id = 7;
+///
+/// let value = json!({
+///     #![sort_keys]
+///     "type": "push",
+///     id,
+///     "€": "euro",
+///     "Z": null,
+/// });
+///
+/// assert_eq!(
+///     serde_json::to_string(&value).unwrap(),
+///     r#"{"Z":null,"id":7,"type":"push","€":"euro"}"#,
+/// );
+/// # }
+/// ```
+///
+/// JSON that is already encoded can be spliced in verbatim with
+/// `raw!("...")`, which takes a compile-time string and checks that it is
+/// valid JSON while compiling. It is written as is in pretty output too,
+/// without being indented. See [`Raw`](crate::Raw) for fragments that are
+/// only known at runtime.
+///
+/// ```
+/// # use typed_json::json;
+/// #
+/// let value = json!({ "id": 1, "defaults": raw!(r#"{"theme": "dark"}"#) });
+///
+/// assert_eq!(
+///     serde_json::to_string(&value).unwrap(),
+///     r#"{"id":1,"defaults":{"theme": "dark"}}"#,
+/// );
+/// ```
+///
+/// The same literal key can only appear once in an object, anything else is
+/// a compile error pointing at the repeated key. Keys of conditional entries
+/// are only checked against the unconditional keys before them, since the
+/// conditions may never both be true. Keys that are only known at runtime can
+/// be checked while serializing with [`Strict`](crate::Strict).
+///
+/// ```compile_fail
+/// # use typed_json::json;
+/// #
+/// let value = json!({
+///     "id": 1,
+///     "id": 2,
+/// });
+/// ```
+///
 /// Trailing commas are allowed inside both arrays and objects.
 ///
 /// ```
@@ -50,19 +334,48 @@
 ///     "comma -->",
 /// ]);
 /// ```
+///
+/// A syntax error is reported at the token where it happens, but only the
+/// `proc-macro` feature says what is wrong and how to fix it. The default
+/// `macro_rules!` backend can only give rustc's own errors, like ``no rules
+/// expected `200` `` or `unexpected end of macro invocation`, with a note on
+/// what was expected there, like the `,` after an element or the `:` after a
+/// key. A `compile_error!` from a `macro_rules!` would point at the whole
+/// `json!` call rather than the token. A stable proc macro can't attach a
+/// `help` note to an error either, so the fix is part of its one-line message.
 #[macro_export(local_inner_macros)]
 macro_rules! json {
     // Hide distracting implementation details from the generated rustdoc.
+    ($($json:tt)+) => {
+        json_impl!($($json)+)
+    };
+}
+
+#[cfg(not(feature = "proc-macro"))]
+#[macro_export(local_inner_macros)]
+#[doc(hidden)]
+macro_rules! json_impl {
     ($($json:tt)+) => {
         json_internal!($($json)+)
     };
 }
 
+// Parses the whole literal in one pass, instead of one `macro_rules!` step per
+// token, and so needs no `#![recursion_limit]`.
+#[cfg(feature = "proc-macro")]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! json_impl {
+    ($($json:tt)+) => {
+        $crate::__private::json!($crate, $($json)+)
+    };
+}
+
 #[macro_export(local_inner_macros)]
 #[doc(hidden)]
 macro_rules! json_internal {
     //////////////////////////////////////////////////////////////////////////
-    // TT muncher for parsing the inside of an array [...]. Produces a vec![...]
+    // TT muncher for parsing the inside of an array [...]. Produces an hlist![...]
     // of the elements.
     //
     // Must be invoked as: json_internal!(@array [] $($tt)*)
@@ -70,47 +383,87 @@ macro_rules! json_internal {
 
     // Done with trailing comma.
     (@array [$($elems:expr,)*]) => {
-        json_internal_vec![$($elems,)*]
+        hlist![$($elems),*]
     };
 
     // Done without trailing comma.
     (@array [$($elems:expr),*]) => {
-        json_internal_vec![$($elems),*]
+        hlist![$($elems),*]
     };
 
     // Next element is `null`.
     (@array [$($elems:expr,)*] null $($rest:tt)*) => {
-        json_internal!(@array [$($elems,)* json_internal!(null)] $($rest)*)
+        json_internal!(@array [$($elems,)* json_internal!(@item null)] $($rest)*)
     };
 
     // Next element is `true`.
     (@array [$($elems:expr,)*] true $($rest:tt)*) => {
-        json_internal!(@array [$($elems,)* json_internal!(true)] $($rest)*)
+        json_internal!(@array [$($elems,)* json_internal!(@item true)] $($rest)*)
     };
 
     // Next element is `false`.
     (@array [$($elems:expr,)*] false $($rest:tt)*) => {
-        json_internal!(@array [$($elems,)* json_internal!(false)] $($rest)*)
+        json_internal!(@array [$($elems,)* json_internal!(@item false)] $($rest)*)
     };
 
     // Next element is an array.
     (@array [$($elems:expr,)*] [$($array:tt)*] $($rest:tt)*) => {
-        json_internal!(@array [$($elems,)* json_internal!([$($array)*])] $($rest)*)
+        json_internal!(@array [$($elems,)* json_internal!(@item [$($array)*])] $($rest)*)
     };
 
     // Next element is a map.
     (@array [$($elems:expr,)*] {$($map:tt)*} $($rest:tt)*) => {
-        json_internal!(@array [$($elems,)* json_internal!({$($map)*})] $($rest)*)
+        json_internal!(@array [$($elems,)* json_internal!(@item {$($map)*})] $($rest)*)
+    };
+
+    // Next element spreads the items of an iterator into this array.
+    (@array [$($elems:expr,)*] .. $next:expr, $($rest:tt)*) => {
+        json_internal!(@array [$($elems,)* $crate::__private::Spread($next),] $($rest)*)
+    };
+
+    // Last element spreads the items of an iterator with no trailing comma.
+    (@array [$($elems:expr,)*] .. $last:expr) => {
+        json_internal!(@array [$($elems,)* $crate::__private::Spread($last)])
+    };
+
+    // Next element is a formatted string.
+    (@array [$($elems:expr,)*] f!($($fmt:tt)+) $($rest:tt)*) => {
+        json_internal!(@array [$($elems,)* json_internal!(@item f!($($fmt)+))] $($rest)*)
+    };
+
+    // Next element is a formatted string.
+    (@array [$($elems:expr,)*] format_args!($($fmt:tt)+) $($rest:tt)*) => {
+        json_internal!(@array [$($elems,)* json_internal!(@item f!($($fmt)+))] $($rest)*)
+    };
+
+    // Next element is a raw JSON fragment.
+    (@array [$($elems:expr,)*] raw!($($json:tt)+) $($rest:tt)*) => {
+        json_internal!(@array [$($elems,)* json_internal!(@item raw!($($json)+))] $($rest)*)
+    };
+
+    // Next element may be conditional, `if cond => value`.
+    (@array [$($elems:expr,)*] if $($rest:tt)*) => {
+        json_internal!(@array_if [$($elems,)*] () $($rest)*)
+    };
+
+    // Next element branches on a `match`.
+    (@array [$($elems:expr,)*] match $($rest:tt)*) => {
+        json_internal!(@array_branch [$($elems,)*] (match) $($rest)*)
+    };
+
+    // Next element has outer attributes, like `#[cfg(...)]`.
+    (@array [$($elems:expr,)*] # [$($attr:tt)*] $($rest:tt)*) => {
+        json_internal!(@array_attrs [$($elems,)*] [#[$($attr)*]] () $($rest)*)
     };
 
     // Next element is an expression followed by comma.
     (@array [$($elems:expr,)*] $next:expr, $($rest:tt)*) => {
-        json_internal!(@array [$($elems,)* json_internal!($next),] $($rest)*)
+        json_internal!(@array [$($elems,)* json_internal!(@item $next),] $($rest)*)
     };
 
     // Last element is an expression with no trailing comma.
     (@array [$($elems:expr,)*] $last:expr) => {
-        json_internal!(@array [$($elems,)* json_internal!($last)])
+        json_internal!(@array [$($elems,)* json_internal!(@item $last)])
     };
 
     // Comma after the most recent element.
@@ -118,112 +471,705 @@ macro_rules! json_internal {
         json_internal!(@array [$($elems,)*] $($rest)*)
     };
 
+    // Next element isn't an expression, or is missing the comma after it.
+    // Trigger a reasonable error message.
+    (@array [$($elems:expr,)*] $($rest:tt)+) => {
+        // "no rules expected `x`" with "help: missing comma here"
+        json_expect_expr_comma!($($rest)+)
+    };
+
     // Unexpected token after most recent element.
-    (@array [$($elems:expr),*] $unexpected:tt $($rest:tt)*) => {
-        json_unexpected!($unexpected)
+    (@array [$($elems:expr),*] $($rest:tt)+) => {
+        // "no rules expected `x`" while trying to match `,`
+        json_expect_comma!($($rest)+)
+    };
+
+    // A single element of the array.
+    (@item $($value:tt)+) => {
+        ::core::option::Option::Some(json_internal!($($value)+))
+    };
+
+    // An element that is left out of the array when the condition is false.
+    (@item_if ($($cond:tt)+) $($value:tt)+) => {
+        if $($cond)+ {
+            ::core::option::Option::Some(json_internal!($($value)+))
+        } else {
+            ::core::option::Option::None
+        }
+    };
+
+    //////////////////////////////////////////////////////////////////////////
+    // TT muncher for parsing a conditional array element. The condition is
+    // everything up to the `=>`, the element is everything up to the next
+    // comma.
+    //
+    // Must be invoked as: json_internal!(@array_if [$($elems,)*] () $($tt)*)
+    //////////////////////////////////////////////////////////////////////////
+
+    // End of the condition.
+    (@array_if [$($elems:expr,)*] ($($cond:tt)+) => $($rest:tt)*) => {
+        json_internal!(@array_if_value [$($elems,)*] ($($cond)+) () $($rest)*)
+    };
+
+    // No `=>` before the comma, this is an `if` expression instead.
+    (@array_if [$($elems:expr,)*] ($($cond:tt)*) , $($rest:tt)*) => {
+        json_internal!(@array [$($elems,)* json_internal!(@item if $($cond)*),] $($rest)*)
+    };
+
+    // No `=>` before the end, this is an `if` expression instead.
+    (@array_if [$($elems:expr,)*] ($($cond:tt)*)) => {
+        json_internal!(@array [$($elems,)* json_internal!(@item if $($cond)*)])
+    };
+
+    // Munch a token into the condition.
+    (@array_if [$($elems:expr,)*] ($($cond:tt)*) $tt:tt $($rest:tt)*) => {
+        json_internal!(@array_if [$($elems,)*] ($($cond)* $tt) $($rest)*)
+    };
+
+    // Conditional element followed by comma.
+    (@array_if_value [$($elems:expr,)*] $cond:tt ($($value:tt)+) , $($rest:tt)*) => {
+        json_internal!(@array [$($elems,)* json_internal!(@item_if $cond $($value)+),] $($rest)*)
+    };
+
+    // Last element is conditional with no trailing comma.
+    (@array_if_value [$($elems:expr,)*] $cond:tt ($($value:tt)+)) => {
+        json_internal!(@array [$($elems,)* json_internal!(@item_if $cond $($value)+)])
+    };
+
+    // Missing value after `=>`.
+    (@array_if_value [$($elems:expr,)*] $cond:tt () $(, $($rest:tt)*)?) => {
+        // "unexpected end of macro invocation"
+        json_internal!()
+    };
+
+    // Munch a token into the conditional element.
+    (@array_if_value [$($elems:expr,)*] $cond:tt ($($value:tt)*) $tt:tt $($rest:tt)*) => {
+        json_internal!(@array_if_value [$($elems,)*] $cond ($($value)* $tt) $($rest)*)
+    };
+
+    //////////////////////////////////////////////////////////////////////////
+    // TT muncher for parsing an array element with outer attributes. The
+    // element is everything up to the next comma, parsed as an array of its
+    // own and inserted like an object entry with attributes.
+    //
+    // Must be invoked as: json_internal!(@array_attrs [$($elems,)*] [$($attrs)*] () $($tt)*)
+    //////////////////////////////////////////////////////////////////////////
+
+    // More attributes before the element.
+    (@array_attrs $elems:tt [$($attrs:tt)*] () # [$($attr:tt)*] $($rest:tt)*) => {
+        json_internal!(@array_attrs $elems [$($attrs)* #[$($attr)*]] () $($rest)*)
+    };
+
+    // Missing element after the attributes.
+    (@array_attrs $elems:tt $attrs:tt () $(, $($rest:tt)*)?) => {
+        // "unexpected end of macro invocation"
+        json_internal!()
+    };
+
+    // Element with attributes, followed by comma.
+    (@array_attrs [$($elems:expr,)*] $attrs:tt ($($value:tt)+) , $($rest:tt)*) => {
+        json_internal!(@array [$($elems,)* json_internal!(@attrs $attrs json_internal!(@array [] $($value)+)),] $($rest)*)
+    };
+
+    // Last element with attributes, with no trailing comma.
+    (@array_attrs [$($elems:expr,)*] $attrs:tt ($($value:tt)+)) => {
+        json_internal!(@array [$($elems,)* json_internal!(@attrs $attrs json_internal!(@array [] $($value)+))])
+    };
+
+    // Munch a token into the element.
+    (@array_attrs $elems:tt $attrs:tt ($($value:tt)*) $tt:tt $($rest:tt)*) => {
+        json_internal!(@array_attrs $elems $attrs ($($value)* $tt) $($rest)*)
+    };
+
+    //////////////////////////////////////////////////////////////////////////
+    // TT muncher for parsing an array element that branches on a `match`.
+    // The element is everything up to the next comma.
+    //
+    // Must be invoked as: json_internal!(@array_branch [$($elems,)*] (match) $($tt)*)
+    //////////////////////////////////////////////////////////////////////////
+
+    // Branch followed by comma.
+    (@array_branch [$($elems:expr,)*] ($($value:tt)+) , $($rest:tt)*) => {
+        json_internal!(@array [$($elems,)* json_internal!(@item $($value)+),] $($rest)*)
+    };
+
+    // Last element is a branch with no trailing comma.
+    (@array_branch [$($elems:expr,)*] ($($value:tt)+)) => {
+        json_internal!(@array [$($elems,)* json_internal!(@item $($value)+)])
+    };
+
+    // Munch a token into the branch.
+    (@array_branch [$($elems:expr,)*] ($($value:tt)*) $tt:tt $($rest:tt)*) => {
+        json_internal!(@array_branch [$($elems,)*] ($($value)* $tt) $($rest)*)
     };
 
     //////////////////////////////////////////////////////////////////////////
     // TT muncher for parsing the inside of an object {...}. Each entry is
     // inserted into the given map variable.
     //
-    // Must be invoked as: json_internal!(@object $map () ($($tt)*) ($($tt)*))
+    // Must be invoked as: json_internal!(@object $cx [] () ($($tt)*) ($($tt)*))
+    //
+    // The $cx is ($case, [$($seen),*], [$($attrs)*]). The case is what
+    // shorthand keys are renamed to, and the seen keys are the literal and
+    // shorthand keys so far, used to reject duplicates at compile time. The
+    // attrs are the outer attributes of the entry being parsed.
     //
     // We require two copies of the input tokens so that we can match on one
     // copy and trigger errors on the other copy.
     //////////////////////////////////////////////////////////////////////////
 
     // Done.
-    (@object [$($elems:expr),*] () () ()) => { json_internal_vec![$($elems),*] };
+    (@object ($case:expr, $seen:tt, []) [$($elems:expr),*] () () ()) => { hlist![$($elems),*] };
+
+    // Attributes with no entry after them.
+    (@object $cx:tt [$($elems:expr),*] () () ()) => {
+        // "unexpected end of macro invocation"
+        json_internal!()
+    };
 
-    (@object [$($elems:expr),*] [$($key:tt)+] ($value:expr) $(,)?) => {
-        json_internal_vec![$($elems,)* $crate::__private::KV::Pair(json_internal!($($key)*), $value)]
+    // Insert the last entry without trailing comma.
+    (@object $cx:tt [$($elems:expr),*] [$($key:tt)+] ($value:expr) $(,)?) => {
+        json_internal!(@insert $cx [$($elems),*] entry [$($key)+] $value, ())
     };
 
     // Insert the current entry followed by trailing comma.
-    (@object [$($elems:expr),*] [$($key:tt)+] ($value:expr) , $($rest:tt)*) => {
-        json_internal!(@object [$($elems,)* $crate::__private::KV::Pair(json_internal!($($key)*), $value)] () ($($rest)*) ($($rest)*))
+    (@object $cx:tt [$($elems:expr),*] [$($key:tt)+] ($value:expr) , $($rest:tt)*) => {
+        json_internal!(@insert $cx [$($elems),*] entry [$($key)+] $value, ($($rest)*))
     };
 
     // Current entry followed by unexpected token.
-    (@object [$($elems:expr),*] [$($key:tt)+] ($value:expr) $unexpected:tt $($rest:tt)*) => {
-        json_unexpected!($unexpected)
+    (@object $cx:tt [$($elems:expr),*] [$($key:tt)+] ($value:expr) $($rest:tt)+) => {
+        // "no rules expected `x`" while trying to match `,`
+        json_expect_comma!($($rest)+)
     };
 
     // Next value is `null`.
-    (@object [$($elems:expr),*] ($($key:tt)+) (: null $($rest:tt)*) $copy:tt) => {
-        json_internal!(@object [$($elems),*] [$($key)+] (json_internal!(null)) $($rest)*)
+    (@object $cx:tt [$($elems:expr),*] ($($key:tt)+) (: null $($rest:tt)*) $copy:tt) => {
+        json_internal!(@object $cx [$($elems),*] [$($key)+] (json_internal!(null)) $($rest)*)
     };
 
     // Next value is `true`.
-    (@object [$($elems:expr),*] ($($key:tt)+) (: true $($rest:tt)*) $copy:tt) => {
-        json_internal!(@object [$($elems),*] [$($key)+] (json_internal!(true)) $($rest)*)
+    (@object $cx:tt [$($elems:expr),*] ($($key:tt)+) (: true $($rest:tt)*) $copy:tt) => {
+        json_internal!(@object $cx [$($elems),*] [$($key)+] (json_internal!(true)) $($rest)*)
     };
 
     // Next value is `false`.
-    (@object [$($elems:expr),*] ($($key:tt)+) (: false $($rest:tt)*) $copy:tt) => {
-        json_internal!(@object [$($elems),*] [$($key)+] (json_internal!(false)) $($rest)*)
+    (@object $cx:tt [$($elems:expr),*] ($($key:tt)+) (: false $($rest:tt)*) $copy:tt) => {
+        json_internal!(@object $cx [$($elems),*] [$($key)+] (json_internal!(false)) $($rest)*)
     };
 
     // Next value is an array.
-    (@object [$($elems:expr),*] ($($key:tt)+) (: [$($array:tt)*] $($rest:tt)*) $copy:tt) => {
-        json_internal!(@object [$($elems),*] [$($key)+] (json_internal!([$($array)*])) $($rest)*)
+    (@object $cx:tt [$($elems:expr),*] ($($key:tt)+) (: [$($array:tt)*] $($rest:tt)*) $copy:tt) => {
+        json_internal!(@object $cx [$($elems),*] [$($key)+] (json_internal!([$($array)*])) $($rest)*)
     };
 
     // Next value is a map.
-    (@object [$($elems:expr),*] ($($key:tt)+) (: {$($map:tt)*} $($rest:tt)*) $copy:tt) => {
-        json_internal!(@object [$($elems),*] [$($key)+] (json_internal!({$($map)*})) $($rest)*)
+    (@object $cx:tt [$($elems:expr),*] ($($key:tt)+) (: {$($map:tt)*} $($rest:tt)*) $copy:tt) => {
+        json_internal!(@object $cx [$($elems),*] [$($key)+] (json_internal!({$($map)*})) $($rest)*)
+    };
+
+    // Next value is a formatted string.
+    (@object $cx:tt [$($elems:expr),*] ($($key:tt)+) (: f!($($fmt:tt)+) $($rest:tt)*) $copy:tt) => {
+        json_internal!(@object $cx [$($elems),*] [$($key)+] (json_internal!(f!($($fmt)+))) $($rest)*)
+    };
+
+    // Next value is a formatted string.
+    (@object $cx:tt [$($elems:expr),*] ($($key:tt)+) (: format_args!($($fmt:tt)+) $($rest:tt)*) $copy:tt) => {
+        json_internal!(@object $cx [$($elems),*] [$($key)+] (json_internal!(f!($($fmt)+))) $($rest)*)
+    };
+
+    // Next value is a raw JSON fragment.
+    (@object $cx:tt [$($elems:expr),*] ($($key:tt)+) (: raw!($($json:tt)+) $($rest:tt)*) $copy:tt) => {
+        json_internal!(@object $cx [$($elems),*] [$($key)+] (json_internal!(raw!($($json)+))) $($rest)*)
+    };
+
+    // Next value branches on a condition.
+    (@object $cx:tt [$($elems:expr),*] ($($key:tt)+) (: if $($rest:tt)*) $copy:tt) => {
+        json_internal!(@object_branch $cx [$($elems),*] ($($key)+) (if) ($($rest)*))
+    };
+
+    // Next value branches on a `match`.
+    (@object $cx:tt [$($elems:expr),*] ($($key:tt)+) (: match $($rest:tt)*) $copy:tt) => {
+        json_internal!(@object_branch $cx [$($elems),*] ($($key)+) (match) ($($rest)*))
     };
 
     // Next value is an expression followed by comma.
-    (@object [$($elems:expr),*] ($($key:tt)+) (: $value:expr , $($rest:tt)*) $copy:tt) => {
-        json_internal!(@object [$($elems),*] [$($key)+] (json_internal!($value)) , $($rest)*)
+    (@object $cx:tt [$($elems:expr),*] ($($key:tt)+) (: $value:expr , $($rest:tt)*) $copy:tt) => {
+        json_internal!(@object $cx [$($elems),*] [$($key)+] (json_internal!($value)) , $($rest)*)
     };
 
     // Last value is an expression with no trailing comma.
-    (@object [$($elems:expr),*] ($($key:tt)+) (: $value:expr) $copy:tt) => {
-        json_internal!(@object [$($elems),*] [$($key)+] (json_internal!($value)))
+    (@object $cx:tt [$($elems:expr),*] ($($key:tt)+) (: $value:expr) $copy:tt) => {
+        json_internal!(@object $cx [$($elems),*] [$($key)+] (json_internal!($value)))
+    };
+
+    // Next value is an optional expression followed by comma.
+    (@object $cx:tt [$($elems:expr),*] ($($key:tt)+) (?: $value:expr , $($rest:tt)*) $copy:tt) => {
+        json_internal!(@insert $cx [$($elems),*] optional [$($key)+] $value, ($($rest)*))
+    };
+
+    // Last value is an optional expression with no trailing comma.
+    (@object $cx:tt [$($elems:expr),*] ($($key:tt)+) (?: $value:expr) $copy:tt) => {
+        json_internal!(@insert $cx [$($elems),*] optional [$($key)+] $value, ())
     };
 
     // Missing value for last entry. Trigger a reasonable error message.
-    (@object [$($elems:expr),*] ($($key:tt)+) (:) $copy:tt) => {
-        // "unexpected end of macro invocation"
-        json_internal!()
+    (@object $cx:tt [$($elems:expr),*] ($($key:tt)+) (:) $copy:tt) => {
+        // "unexpected end of macro invocation" while trying to match `$e:expr`
+        json_expect_expr_comma!()
     };
 
     // Missing colon and value for last entry. Trigger a reasonable error
     // message.
-    (@object [$($elems:expr),*] ($($key:tt)+) () $copy:tt) => {
-        // "unexpected end of macro invocation"
-        json_internal!()
+    (@object $cx:tt [$($elems:expr),*] ($($key:tt)+) () $copy:tt) => {
+        // "no rules expected `x`" or "unexpected end of macro invocation"
+        // while trying to match `:`
+        json_expect_colon!($($key)+)
     };
 
     // Misplaced colon. Trigger a reasonable error message.
-    (@object [$($elems:expr),*] () (: $($rest:tt)*) ($colon:tt $($copy:tt)*)) => {
+    (@object $cx:tt [$($elems:expr),*] () (: $($rest:tt)*) ($colon:tt $($copy:tt)*)) => {
         // Takes no arguments so "no rules expected the token `:`".
         json_unexpected!($colon)
     };
 
     // Found a comma inside a key. Trigger a reasonable error message.
-    (@object [$($elems:expr),*] ($($key:tt)*) (, $($rest:tt)*) ($comma:tt $($copy:tt)*)) => {
+    (@object $cx:tt [$($elems:expr),*] ($($key:tt)*) (, $($rest:tt)*) ($comma:tt $($copy:tt)*)) => {
         // Takes no arguments so "no rules expected the token `,`".
         json_unexpected!($comma)
     };
 
     // Key is fully parenthesized. This avoids clippy double_parens false
     // positives because the parenthesization may be necessary here.
-    (@object [$($elems:expr),*] () (($key:expr) : $($rest:tt)*) $copy:tt) => {
-        json_internal!(@object [$($elems),*] ($key) (: $($rest)*) (: $($rest)*))
+    (@object $cx:tt [$($elems:expr),*] () (($key:expr) : $($rest:tt)*) $copy:tt) => {
+        json_internal!(@object $cx [$($elems),*] ($key) (: $($rest)*) (: $($rest)*))
     };
 
     // Refuse to absorb colon token into key expression.
-    (@object [$($elems:expr),*] ($($key:tt)*) (: $($unexpected:tt)+) $copy:tt) => {
+    (@object $cx:tt [$($elems:expr),*] ($($key:tt)*) (: $($unexpected:tt)+) $copy:tt) => {
         json_expect_expr_comma!($($unexpected)+)
     };
 
+    // Next entry has outer attributes, like `#[cfg(...)]`. They are carried
+    // along until the entry is inserted.
+    (@object ($case:expr, $seen:tt, [$($attrs:tt)*]) [$($elems:expr),*] () (# [$($attr:tt)*] $($rest:tt)*) $copy:tt) => {
+        json_internal!(@object ($case, $seen, [$($attrs)* #[$($attr)*]]) [$($elems),*] () ($($rest)*) ($($rest)*))
+    };
+
+    // Next entry spreads an object literal into this one.
+    (@object $cx:tt [$($elems:expr),*] () (.. {$($map:tt)*} $(, $($rest:tt)*)?) $copy:tt) => {
+        json_internal!(@object $cx [$($elems),*] () (.. json_internal!({$($map)*}) $(, $($rest)*)?) $copy)
+    };
+
+    // Next entry spreads another map into this one, followed by comma.
+    (@object ($case:expr, $seen:tt, $attrs:tt) [$($elems:expr),*] () (.. $value:expr , $($rest:tt)*) $copy:tt) => {
+        json_internal!(@object ($case, $seen, []) [$($elems,)* json_internal!(@attrs $attrs $crate::__private::Spread($value))] () ($($rest)*) ($($rest)*))
+    };
+
+    // Last entry spreads another map into this one with no trailing comma.
+    (@object ($case:expr, $seen:tt, $attrs:tt) [$($elems:expr),*] () (.. $value:expr) $copy:tt) => {
+        hlist![$($elems,)* json_internal!(@attrs $attrs $crate::__private::Spread($value))]
+    };
+
+    // Next entry is a comprehension, `for pat in iter => key: value`.
+    (@object $cx:tt [$($elems:expr),*] () (for $pat:pat in $iter:expr => $($rest:tt)*) $copy:tt) => {
+        json_internal!(@object_for $cx [$($elems),*] ($pat, $iter) () ($($rest)*))
+    };
+
+    // Next entry may be conditional, `if cond => key: value`.
+    (@object $cx:tt [$($elems:expr),*] () (if $($rest:tt)*) $copy:tt) => {
+        json_internal!(@object_if $cx [$($elems),*] () ($($rest)*))
+    };
+
+    // Next entry is a shorthand `name`, followed by comma.
+    (@object $cx:tt [$($elems:expr),*] ($(@if $cond:tt)?) ($name:ident , $($rest:tt)*) $copy:tt) => {
+        json_internal!(@insert $cx [$($elems),*] entry [$(@if $cond)? @shorthand $name] json_internal!($name), ($($rest)*))
+    };
+
+    // Last entry is a shorthand `name` with no trailing comma.
+    (@object $cx:tt [$($elems:expr),*] ($(@if $cond:tt)?) ($name:ident) $copy:tt) => {
+        json_internal!(@insert $cx [$($elems),*] entry [$(@if $cond)? @shorthand $name] json_internal!($name), ())
+    };
+
     // Munch a token into the current key.
-    (@object [$($elems:expr),*] ($($key:tt)*) ($tt:tt $($rest:tt)*) $copy:tt) => {
-        json_internal!(@object [$($elems),*] ($($key)* $tt) ($($rest)*) ($($rest)*))
+    (@object $cx:tt [$($elems:expr),*] ($($key:tt)*) ($tt:tt $($rest:tt)*) $copy:tt) => {
+        json_internal!(@object $cx [$($elems),*] ($($key)* $tt) ($($rest)*) ($($rest)*))
+    };
+
+    // A conditional entry of the object.
+    (@entry [@if $cond:tt $($key:tt)+] $value:expr) => {
+        json_internal!(@if $cond json_internal!(@entry [$($key)+] $value))
+    };
+
+    // A single entry with a literal key, which is also written out as `"key":`
+    // for `Display` when it needs no escaping.
+    (@entry [@unique $seen:tt $key:literal] $value:expr) => {
+        ::core::option::Option::Some($crate::__private::KV::Rendered(
+            {
+                const KEY: $crate::__private::Rendered =
+                    $crate::__private::Rendered::new(::core::concat!(::core::stringify!($key), ":"));
+                KEY
+            },
+            json_internal!(@unique $seen $key),
+            $value,
+        ))
+    };
+
+    // A single entry of the object.
+    (@entry [$($key:tt)+] $value:expr) => {
+        ::core::option::Option::Some($crate::__private::KV::Pair(json_internal!($($key)+), $value))
+    };
+
+    // A conditional entry that is left out when the value is `None`.
+    (@optional [@if $cond:tt $($key:tt)+] $value:expr) => {
+        json_internal!(@if $cond json_internal!(@optional [$($key)+] $value))
+    };
+
+    // An entry that is left out of the object when the value is `None`.
+    (@optional [$($key:tt)+] $value:expr) => {
+        $crate::__private::KV::optional(json_internal!($($key)+), $value)
+    };
+
+    // Insert an entry with a literal key. It must not repeat any of the keys
+    // seen before it, and is remembered for the entries that follow.
+    (@insert ($case:expr, [$($seen:expr),*], []) [$($elems:expr),*] $kind:ident [$key:literal] $value:expr, ($($rest:tt)*)) => {
+        json_internal!(@object ($case, [$($seen,)* ::core::concat!($key)], []) [$($elems,)* json_internal!(@$kind [@unique [$($seen),*] $key] $value)] () ($($rest)*) ($($rest)*))
+    };
+
+    // Insert a shorthand entry, checked and remembered like a literal key.
+    (@insert ($case:expr, [$($seen:expr),*], []) [$($elems:expr),*] $kind:ident [@shorthand $name:ident] $value:expr, ($($rest:tt)*)) => {
+        json_internal!(@object ($case, [$($seen,)* json_internal!(@shorthand ($case) $name)], []) [$($elems,)* json_internal!(@$kind [@unique [$($seen),*] @shorthand ($case) $name] $value)] () ($($rest)*) ($($rest)*))
+    };
+
+    // Insert an entry with attributes and a literal key. It may be compiled
+    // out, so like a conditional entry it is checked against the keys seen
+    // before it but not remembered.
+    (@insert ($case:expr, [$($seen:expr),*], $attrs:tt) [$($elems:expr),*] $kind:ident [$key:literal] $value:expr, ($($rest:tt)*)) => {
+        json_internal!(@object ($case, [$($seen),*], []) [$($elems,)* json_internal!(@attrs $attrs json_internal!(@$kind [@unique [$($seen),*] $key] $value))] () ($($rest)*) ($($rest)*))
+    };
+
+    // Insert a shorthand entry with attributes, checked like a literal key
+    // with attributes.
+    (@insert ($case:expr, [$($seen:expr),*], $attrs:tt) [$($elems:expr),*] $kind:ident [@shorthand $name:ident] $value:expr, ($($rest:tt)*)) => {
+        json_internal!(@object ($case, [$($seen),*], []) [$($elems,)* json_internal!(@attrs $attrs json_internal!(@$kind [@unique [$($seen),*] @shorthand ($case) $name] $value))] () ($($rest)*) ($($rest)*))
+    };
+
+    // Insert a conditional entry with a literal key. Conditions may be
+    // mutually exclusive, so it is checked against the keys seen before it
+    // but not remembered.
+    (@insert ($case:expr, [$($seen:expr),*], $attrs:tt) [$($elems:expr),*] $kind:ident [@if $cond:tt $key:literal] $value:expr, ($($rest:tt)*)) => {
+        json_internal!(@object ($case, [$($seen),*], []) [$($elems,)* json_internal!(@attrs $attrs json_internal!(@$kind [@if $cond @unique [$($seen),*] $key] $value))] () ($($rest)*) ($($rest)*))
+    };
+
+    // Insert a conditional shorthand entry, checked like a conditional
+    // literal key.
+    (@insert ($case:expr, [$($seen:expr),*], $attrs:tt) [$($elems:expr),*] $kind:ident [@if $cond:tt @shorthand $name:ident] $value:expr, ($($rest:tt)*)) => {
+        json_internal!(@object ($case, [$($seen),*], []) [$($elems,)* json_internal!(@attrs $attrs json_internal!(@$kind [@if $cond @unique [$($seen),*] @shorthand ($case) $name] $value))] () ($($rest)*) ($($rest)*))
+    };
+
+    // Insert an entry with a key that is only known at runtime.
+    (@insert ($case:expr, $seen:tt, $attrs:tt) [$($elems:expr),*] $kind:ident [$($key:tt)+] $value:expr, ($($rest:tt)*)) => {
+        json_internal!(@object ($case, $seen, []) [$($elems,)* json_internal!(@attrs $attrs json_internal!(@$kind [$($key)+] $value))] () ($($rest)*) ($($rest)*))
+    };
+
+    // An entry with no outer attributes.
+    (@attrs [] $entry:expr) => {
+        $entry
+    };
+
+    // An entry with outer attributes, which apply to a `let` of it. When
+    // `#[cfg(...)]` compiles it out, a `()` that adds nothing to the object or array
+    // is left in its place.
+    (@attrs [$($attr:tt)+] $entry:expr) => {{
+        #[allow(unused_variables, clippy::let_unit_value)]
+        let entry = ();
+        $($attr)+
+        let entry = $entry;
+        entry
+    }};
+
+    // A literal key that is a compile error if it is the same as any of the
+    // keys seen before it. The error points at the key.
+    (@unique [$($seen:expr),*] $key:literal) => {
+        $crate::__private::Expr({
+            let key: <$crate::__private::Key<{ $crate::__private::contains(&[$($seen),*], ::core::concat!($key)) }> as $crate::__private::Unique<_>>::Key = $key;
+            key
+        })
+    };
+
+    // A shorthand key that is a compile error if it is the same as any of the
+    // keys seen before it.
+    (@unique [$($seen:expr),*] @shorthand $case:tt $name:ident) => {
+        $crate::__private::Expr({
+            #[allow(non_upper_case_globals)]
+            const $name: &str = json_internal!(@shorthand $case $name);
+            let key: <$crate::__private::Key<{ $crate::__private::contains(&[$($seen),*], $name) }> as $crate::__private::Unique<_>>::Key = $name;
+            key
+        })
+    };
+
+    // The key of a shorthand entry, the name of the variable in the given case.
+    (@shorthand ($case:expr) $name:ident) => {{
+        const CASE: $crate::__private::Case = $case;
+        const KEY: $crate::__private::Renamed<{ CASE.len(::core::stringify!($name)) }> =
+            CASE.convert(::core::stringify!($name));
+        KEY.as_str()
+    }};
+
+    // Entries of the object produced from an iterator.
+    (@entries ($pat:pat, $iter:expr) [$($key:tt)+] $($value:tt)+) => {
+        $crate::__private::Entries::new($iter, |$pat| {
+            (json_internal!($($key)+), json_internal!($($value)+))
+        })
+    };
+
+    // An entry that is left out of the object when the condition is false.
+    (@if ($($cond:tt)+) $entry:expr) => {
+        if $($cond)+ {
+            $entry
+        } else {
+            ::core::option::Option::None
+        }
+    };
+
+    //////////////////////////////////////////////////////////////////////////
+    // TT muncher for parsing the condition of an object entry. The condition
+    // is everything up to the `=>`, after which the entry is parsed as usual
+    // with the condition carried along in front of the key.
+    //
+    // Must be invoked as: json_internal!(@object_if $cx [$($elems),*] () ($($tt)*))
+    //////////////////////////////////////////////////////////////////////////
+
+    // End of the condition.
+    (@object_if $cx:tt [$($elems:expr),*] ($($cond:tt)+) (=> $($rest:tt)*)) => {
+        json_internal!(@object $cx [$($elems),*] (@if ($($cond)+)) ($($rest)*) ($($rest)*))
+    };
+
+    // No `=>` before the colon, this key is an `if` expression instead.
+    (@object_if $cx:tt [$($elems:expr),*] ($($cond:tt)*) (: $($rest:tt)*)) => {
+        json_internal!(@object $cx [$($elems),*] (if $($cond)*) (: $($rest)*) (: $($rest)*))
+    };
+
+    // Missing `=>` and entry.
+    (@object_if $cx:tt [$($elems:expr),*] ($($cond:tt)*) ()) => {
+        // "unexpected end of macro invocation"
+        json_internal!()
+    };
+
+    // Munch a token into the condition.
+    (@object_if $cx:tt [$($elems:expr),*] ($($cond:tt)*) ($tt:tt $($rest:tt)*)) => {
+        json_internal!(@object_if $cx [$($elems),*] ($($cond)* $tt) ($($rest)*))
+    };
+
+    //////////////////////////////////////////////////////////////////////////
+    // TT muncher for parsing an object comprehension after the `=>`. The key
+    // is everything up to the colon, the value is everything up to the next
+    // comma.
+    //
+    // Must be invoked as: json_internal!(@object_for $cx [$($elems),*] ($pat, $iter) () ($($tt)*))
+    //////////////////////////////////////////////////////////////////////////
+
+    // End of the key.
+    (@object_for $cx:tt [$($elems:expr),*] $for:tt ($($key:tt)+) (: $($rest:tt)*)) => {
+        json_internal!(@object_for_value $cx [$($elems),*] $for [$($key)+] () ($($rest)*))
+    };
+
+    // Missing colon and value.
+    (@object_for $cx:tt [$($elems:expr),*] $for:tt ($($key:tt)*) ($(, $($rest:tt)*)?)) => {
+        // "unexpected end of macro invocation"
+        json_internal!()
+    };
+
+    // Munch a token into the key.
+    (@object_for $cx:tt [$($elems:expr),*] $for:tt ($($key:tt)*) ($tt:tt $($rest:tt)*)) => {
+        json_internal!(@object_for $cx [$($elems),*] $for ($($key)* $tt) ($($rest)*))
+    };
+
+    // Comprehension followed by comma.
+    (@object_for_value ($case:expr, $seen:tt, $attrs:tt) [$($elems:expr),*] $for:tt $key:tt ($($value:tt)+) (, $($rest:tt)*)) => {
+        json_internal!(@object ($case, $seen, []) [$($elems,)* json_internal!(@attrs $attrs json_internal!(@entries $for $key $($value)+))] () ($($rest)*) ($($rest)*))
+    };
+
+    // Last entry is a comprehension with no trailing comma.
+    (@object_for_value ($case:expr, $seen:tt, $attrs:tt) [$($elems:expr),*] $for:tt $key:tt ($($value:tt)+) ()) => {
+        hlist![$($elems,)* json_internal!(@attrs $attrs json_internal!(@entries $for $key $($value)+))]
+    };
+
+    // Missing value.
+    (@object_for_value $cx:tt [$($elems:expr),*] $for:tt $key:tt () ($(, $($rest:tt)*)?)) => {
+        // "unexpected end of macro invocation"
+        json_internal!()
+    };
+
+    // Munch a token into the value.
+    (@object_for_value $cx:tt [$($elems:expr),*] $for:tt $key:tt ($($value:tt)*) ($tt:tt $($rest:tt)*)) => {
+        json_internal!(@object_for_value $cx [$($elems),*] $for $key ($($value)* $tt) ($($rest)*))
+    };
+
+    //////////////////////////////////////////////////////////////////////////
+    // TT muncher for parsing an object value that branches on a condition or
+    // a `match`. The value is everything up to the next comma.
+    //
+    // Must be invoked as: json_internal!(@object_branch $cx [$($elems),*] ($($key)+) (if) ($($tt)*))
+    //////////////////////////////////////////////////////////////////////////
+
+    // Branch followed by comma.
+    (@object_branch $cx:tt [$($elems:expr),*] ($($key:tt)+) ($($value:tt)+) (, $($rest:tt)*)) => {
+        json_internal!(@object $cx [$($elems),*] [$($key)+] (json_internal!($($value)+)) , $($rest)*)
+    };
+
+    // Last value is a branch with no trailing comma.
+    (@object_branch $cx:tt [$($elems:expr),*] ($($key:tt)+) ($($value:tt)+) ()) => {
+        json_internal!(@object $cx [$($elems),*] [$($key)+] (json_internal!($($value)+)))
+    };
+
+    // Munch a token into the branch.
+    (@object_branch $cx:tt [$($elems:expr),*] $key:tt ($($value:tt)*) ($tt:tt $($rest:tt)*)) => {
+        json_internal!(@object_branch $cx [$($elems),*] $key ($($value)* $tt) ($($rest)*))
+    };
+
+    //////////////////////////////////////////////////////////////////////////
+    // TT muncher for parsing a value that branches on a condition,
+    // `if cond { value } else { value }`. The condition is everything up to
+    // a block followed by `else`, which can be followed by another `if`.
+    //
+    // Must be invoked as: json_internal!(@branch_if () $($tt)*)
+    //////////////////////////////////////////////////////////////////////////
+
+    // `else if`, which branches again.
+    (@branch_if ($($cond:tt)+) {$($then:tt)+} else if $($rest:tt)+) => {
+        if $($cond)+ {
+            $crate::Either::Left(json_internal!($($then)+))
+        } else {
+            $crate::Either::Right(json_internal!(if $($rest)+))
+        }
+    };
+
+    // `else`, the last branch.
+    (@branch_if ($($cond:tt)+) {$($then:tt)+} else {$($else:tt)+}) => {
+        if $($cond)+ {
+            $crate::Either::Left(json_internal!($($then)+))
+        } else {
+            $crate::Either::Right(json_internal!($($else)+))
+        }
+    };
+
+    // Missing `else`.
+    (@branch_if ($($cond:tt)+) {$($then:tt)+}) => {
+        // "unexpected end of macro invocation"
+        json_internal!()
+    };
+
+    // Munch a token into the condition.
+    (@branch_if ($($cond:tt)*) $tt:tt $($rest:tt)*) => {
+        json_internal!(@branch_if ($($cond)* $tt) $($rest)*)
+    };
+
+    //////////////////////////////////////////////////////////////////////////
+    // TT muncher for parsing a value that branches on a `match`. Each arm is
+    // `pattern => value`, separated by commas. The arms so far are collected
+    // along with an `R` for each of them, the number of `Either::Right`s to
+    // wrap the next arm in.
+    //
+    // Must be invoked as: json_internal!(@match () $($tt)*)
+    //////////////////////////////////////////////////////////////////////////
+
+    // End of the expression being matched on.
+    (@match ($($expr:tt)+) {$($arms:tt)+}) => {
+        json_internal!(@match_arm ($($expr)+) [] [] () ($($arms)+))
+    };
+
+    // Munch a token into the expression being matched on.
+    (@match ($($expr:tt)*) $tt:tt $($rest:tt)*) => {
+        json_internal!(@match ($($expr)* $tt) $($rest)*)
+    };
+
+    // End of the pattern.
+    (@match_arm $expr:tt $arms:tt $right:tt ($($pat:tt)+) (=> $($rest:tt)*)) => {
+        json_internal!(@match_value $expr $arms $right ($($pat)+) () ($($rest)*))
+    };
+
+    // Munch a token into the pattern.
+    (@match_arm $expr:tt $arms:tt $right:tt ($($pat:tt)*) ($tt:tt $($rest:tt)*)) => {
+        json_internal!(@match_arm $expr $arms $right ($($pat)* $tt) ($($rest)*))
+    };
+
+    // Arm followed by comma and more arms.
+    (@match_value $expr:tt [$($arms:tt)*] [$($right:tt)*] ($($pat:tt)+) ($($value:tt)+) (, $($rest:tt)+)) => {
+        json_internal!(@match_arm $expr [
+            $($arms)*
+            $($pat)+ => json_internal!(@either [$($right)*] $crate::Either::Left(json_internal!($($value)+))),
+        ] [$($right)* R] () ($($rest)+))
+    };
+
+    // Last arm, with an optional trailing comma.
+    (@match_value ($($expr:tt)+) [$($arms:tt)*] [$($right:tt)*] ($($pat:tt)+) ($($value:tt)+) ($(,)?)) => {
+        match $($expr)+ {
+            $($arms)*
+            $($pat)+ => json_internal!(@either [$($right)*] json_internal!($($value)+)),
+        }
+    };
+
+    // Missing value after `=>`.
+    (@match_value $expr:tt $arms:tt $right:tt $pat:tt () ($(, $($rest:tt)*)?)) => {
+        // "unexpected end of macro invocation"
+        json_internal!()
+    };
+
+    // Munch a token into the value.
+    (@match_value $expr:tt $arms:tt $right:tt $pat:tt ($($value:tt)*) ($tt:tt $($rest:tt)*)) => {
+        json_internal!(@match_value $expr $arms $right $pat ($($value)* $tt) ($($rest)*))
+    };
+
+    // Wrap the value of an arm in an `Either::Right` for each arm before it.
+    (@either [] $value:expr) => {
+        $value
+    };
+
+    (@either [R $($right:tt)*] $value:expr) => {
+        $crate::Either::Right(json_internal!(@either [$($right)*] $value))
+    };
+
+    //////////////////////////////////////////////////////////////////////////
+    // The cases supported by `#![rename_all = "..."]`, the same as serde.
+    //////////////////////////////////////////////////////////////////////////
+
+    (@rename_all "lowercase" $($object:tt)*) => {
+        json_internal!(@object ($crate::__private::Case::Lower, [], []) $($object)*)
+    };
+
+    (@rename_all "UPPERCASE" $($object:tt)*) => {
+        json_internal!(@object ($crate::__private::Case::Upper, [], []) $($object)*)
+    };
+
+    (@rename_all "PascalCase" $($object:tt)*) => {
+        json_internal!(@object ($crate::__private::Case::Pascal, [], []) $($object)*)
+    };
+
+    (@rename_all "camelCase" $($object:tt)*) => {
+        json_internal!(@object ($crate::__private::Case::Camel, [], []) $($object)*)
+    };
+
+    (@rename_all "snake_case" $($object:tt)*) => {
+        json_internal!(@object ($crate::__private::Case::Snake, [], []) $($object)*)
+    };
+
+    (@rename_all "SCREAMING_SNAKE_CASE" $($object:tt)*) => {
+        json_internal!(@object ($crate::__private::Case::ScreamingSnake, [], []) $($object)*)
+    };
+
+    (@rename_all "kebab-case" $($object:tt)*) => {
+        json_internal!(@object ($crate::__private::Case::Kebab, [], []) $($object)*)
+    };
+
+    (@rename_all "SCREAMING-KEBAB-CASE" $($object:tt)*) => {
+        json_internal!(@object ($crate::__private::Case::ScreamingKebab, [], []) $($object)*)
+    };
+
+    // Unknown case. Trigger a reasonable error message.
+    (@rename_all $case:tt $($object:tt)*) => {
+        // Takes no arguments so "no rules expected the token `"..."`".
+        json_unexpected!($case)
     };
 
     //////////////////////////////////////////////////////////////////////////
@@ -244,10 +1190,36 @@ macro_rules! json_internal {
         $crate::__private::Expr(false)
     };
 
+    (f!($($fmt:tt)+)) => {
+        $crate::__private::Expr($crate::__private::Fmt::new(|f: &mut ::core::fmt::Formatter<'_>| {
+            ::core::write!(f, $($fmt)+)
+        }))
+    };
+
+    (format_args!($($fmt:tt)+)) => {
+        json_internal!(f!($($fmt)+))
+    };
+
+    // The string is checked at compile time, and has to be an `InvalidJson`
+    // if it isn't valid JSON, so the error points at it.
+    (raw!($json:expr)) => {
+        $crate::__private::Expr({
+            const RAW: $crate::Raw<'static> = {
+                let _: <$crate::__private::Json<{ $crate::__private::is_valid($json) }> as $crate::__private::Valid>::Str = $json;
+                $crate::Raw::new_unchecked($json)
+            };
+            RAW
+        })
+    };
+
     ([]) => {
         $crate::__private::Array(())
     };
 
+    ([for $pat:pat in $iter:expr => $($value:tt)+]) => {
+        $crate::Comprehension::new($iter, |$pat| json_internal!($($value)+))
+    };
+
     ([ $($tt:tt)+ ]) => {
         $crate::__private::Array(json_internal!(@array [] $($tt)+))
     };
@@ -256,8 +1228,25 @@ macro_rules! json_internal {
         $crate::__private::Map(())
     };
 
+    // Sorting the entries needs every key at once, which only the proc macro has.
+    ({ $(#![rename_all = $case:tt])? #![sort_keys] $($tt:tt)* }) => {
+        ::core::compile_error!("`#![sort_keys]` needs the `proc-macro` feature of typed-json")
+    };
+
+    ({ #![rename_all = $case:tt] $($tt:tt)* }) => {
+        $crate::__private::Map(json_internal!(@rename_all $case [] () ($($tt)*) ($($tt)*)))
+    };
+
     ({ $($tt:tt)+ }) => {
-        $crate::__private::Map(json_internal!(@object [] () ($($tt)+) ($($tt)+)))
+        $crate::__private::Map(json_internal!(@object ($crate::__private::Case::None, [], []) [] () ($($tt)+) ($($tt)+)))
+    };
+
+    (if $($tt:tt)+) => {
+        json_internal!(@branch_if () $($tt)+)
+    };
+
+    (match $($tt:tt)+) => {
+        json_internal!(@match () $($tt)+)
     };
 
     // Any Serialize type: numbers, strings, struct literals, variables etc.
@@ -267,16 +1256,156 @@ macro_rules! json_internal {
     };
 }
 
-// The json_internal macro above cannot invoke vec directly because it uses
-// local_inner_macros. A vec invocation there would resolve to $crate::__private::vec.
-// Instead invoke vec here outside of local_inner_macros.
-#[macro_export(local_inner_macros)]
-#[doc(hidden)]
-macro_rules! json_internal_vec {
-    ($($g:expr),* $(,)?) => {
-        $crate::hlist![
-            $(::core::option::Option::Some($g)),*
-        ]
+/// Parse a JSON string literal at compile time into the same type as [`json!`].
+///
+/// The JSON is checked while compiling, so a syntax error or a repeated key is
+/// a compile error. A `{{expr}}` placeholder can stand in for any value or key,
+/// and is replaced by the Rust expression inside it, just like interpolating
+/// into [`json!`].
+///
+/// ```
+/// # use typed_json::json_str;
+/// #
+/// let code = 200;
+///
+/// let value = json_str!(r#"{
+///     "code": {{code}},
+///     "payload": { "features": ["serde", "json"], "homepage": null }
+/// }"#);
+///
+/// assert_eq!(
+///     serde_json::to_string(&value).unwrap(),
+///     r#"{"code":200,"payload":{"features":["serde","json"],"homepage":null}}"#,
+/// );
+/// ```
+#[cfg(feature = "macros")]
+#[macro_export]
+macro_rules! json_str {
+    ($($json:tt)+) => {
+        $crate::__private::json_str!($crate, $($json)+)
+    };
+}
+
+/// Parse a JSON file at compile time into the same type as [`json!`].
+///
+/// The path is relative to the `Cargo.toml` of the crate calling the macro,
+/// and the crate is rebuilt when the file changes. The file can contain
+/// `{{expr}}` placeholders, see [`json_str!`].
+///
+/// ```ignore
+/// # use typed_json::include_json;
+/// #
+/// let name = "my-service";
+///
+/// // fixtures/service.json: { "kind": "Service", "metadata": { "name": {{name}} } }
+/// let value = include_json!("fixtures/service.json");
+/// ```
+#[cfg(feature = "macros")]
+#[macro_export]
+macro_rules! include_json {
+    ($($path:tt)+) => {
+        $crate::__private::include_json!($crate, $($path)+)
+    };
+}
+
+/// Render a `json!` literal made only of literals to JSON at compile time.
+///
+/// This takes the same syntax as [`json!`], but every value has to be a literal: strings,
+/// numbers, `true`, `false`, `null`, `raw!("...")`, or arrays and objects of them, with string
+/// literal keys. The result is a [`ConstJson`](crate::ConstJson) with both the compact and the
+/// pretty JSON as `&'static str`, so it can be a `const`, and it serializes by splicing in the
+/// compact JSON.
+///
+/// **Only `serde_json` and this crate's own [`Serializer`](crate::Serializer) splice it in.**
+/// Any other serializer, like `serde_test`, RON or YAML, sees a struct named
+/// `$serde_json::private::RawValue` with the JSON text in a field, the same as for
+/// [`Raw`](crate::Raw). Serialize [`ConstJson::parsed`](crate::ConstJson::parsed) for those
+/// instead, which writes the document value by value.
+///
+/// The output is the same as `serde_json` would write, except that floats are always written in
+/// their shortest form, like `1e16`, where some versions of `serde_json` write `1e+16`.
+///
+/// ```
+/// # use typed_json::{json, json_const, ConstJson};
+/// #
+/// const HEALTH: ConstJson = json_const!({ "status": "ok", "checks": ["db", "cache"] });
+/// const BODY: &str = HEALTH.compact();
+///
+/// assert_eq!(BODY, r#"{"status":"ok","checks":["db","cache"]}"#);
+/// assert_eq!(
+///     HEALTH.pretty(),
+///     serde_json::to_string_pretty(&json!({ "status": "ok", "checks": ["db", "cache"] })).unwrap(),
+/// );
+///
+/// let response = json!({ "code": 200, "body": HEALTH });
+/// assert_eq!(
+///     serde_json::to_string(&response).unwrap(),
+///     r#"{"code":200,"body":{"status":"ok","checks":["db","cache"]}}"#,
+/// );
+/// ```
+///
+/// Anything only known at runtime is an error:
+///
+/// ```compile_fail
+/// # use typed_json::json_const;
+/// #
+/// let code = 200;
+/// let value = json_const!({ "code": code });
+/// ```
+#[cfg(feature = "macros")]
+#[macro_export]
+macro_rules! json_const {
+    ($($json:tt)+) => {
+        $crate::__private::json_const!($crate, $($json)+)
+    };
+}
+
+/// Spell out the type of a [`json!`] literal, so that it can be named in a struct field, an
+/// associated type or a `static`.
+///
+/// The template is written like the literal it describes, with each interpolated expression
+/// replaced by its type. `null`, literals, `raw!(...)`, arrays and objects are written the same
+/// as in `json!`, along with:
+///
+/// - `"key"?: T`, an entry that is only present if its `Option<T>` is `Some`
+/// - `(K): T`, an entry whose key is interpolated, of type `K`
+/// - `..T`, a map or iterator of type `T` spread into an object or array
+/// - `[T; N]`, an array of `N` elements of type `T`
+///
+/// A conditional entry or element has the same type whether or not it is present, so it is
+/// written without its `if cond =>`. An entry or element with `#[cfg(...)]` keeps its attribute,
+/// so that the type is compiled in or out along with it. An object with `#![sort_keys]` keeps it too, so
+/// that its entries are sorted the same way.
+///
+/// ```
+/// # use typed_json::{json, json_type};
+/// #
+/// struct Event<'a> {
+///     body: json_type!({ "id": u64, "tags": [&'a str; 2] }),
+/// }
+///
+/// const CODE: u16 = 200;
+/// static HEALTH: json_type!({ "status": "ok", "code": u16 }) = json!({ "status": "ok", "code": CODE });
+///
+/// let tags = ["serde", "json"];
+/// let event = Event {
+///     body: json!({ "id": 7, "tags": [tags[0], tags[1]] }),
+/// };
+///
+/// assert_eq!(
+///     serde_json::to_string(&event.body).unwrap(),
+///     r#"{"id":7,"tags":["serde","json"]}"#,
+/// );
+/// assert_eq!(serde_json::to_string(&HEALTH).unwrap(), r#"{"status":"ok","code":200}"#);
+/// ```
+///
+/// A literal in the document can be the same literal in the template, or its type. `f!` and
+/// comprehensions contain closures, so their types can't be named at all.
+#[cfg(feature = "macros")]
+#[macro_export]
+macro_rules! json_type {
+    ($($json:tt)+) => {
+        $crate::__private::json_type!($crate, $($json)+)
     };
 }
 
@@ -292,6 +1421,18 @@ macro_rules! json_expect_expr_comma {
     ($e:expr , $($tt:tt)*) => {};
 }
 
+#[macro_export]
+#[doc(hidden)]
+macro_rules! json_expect_comma {
+    (, $($tt:tt)*) => {};
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! json_expect_colon {
+    ($key:tt : $($tt:tt)*) => {};
+}
+
 #[macro_export]
 #[doc(hidden)]
 macro_rules! hlist {
@@ -1,15 +1,96 @@
-use crate::DeShared;
+use core::fmt;
 
-#[doc(hidden)]
+use crate::raw::string;
+use crate::ser::{Compact, Compound, Error};
+use crate::spread::{FlatMapSerializer, Spread};
+use crate::{exact_len, DeShared, Expr, JsonKey};
+
+/// An entry of a [`Map`].
 #[derive(Clone, Copy)]
-pub enum KV<T, U> {
+pub enum KV<T: JsonKey, U> {
+    /// The key and its value.
     Pair(T, U),
+    /// Only the value, once the key has been deserialized.
+    #[doc(hidden)]
     V(U),
+    /// The key and its value, along with the key already written out as `"key":` by
+    /// [`json!`](crate::json). `Display` writes it as is.
+    #[doc(hidden)]
+    Rendered(Rendered, T, U),
+}
+
+impl<T: JsonKey, U> KV<T, Expr<U>> {
+    /// An entry that is only present if the value is `Some`.
+    #[doc(hidden)]
+    #[inline]
+    pub fn optional(key: T, value: Option<U>) -> Option<Self> {
+        value.map(|value| KV::Pair(key, Expr(value)))
+    }
+}
+
+/// The `"key":` of an entry with a literal key, which is only ever valid JSON.
+#[doc(hidden)]
+#[derive(Clone, Copy)]
+pub struct Rendered(&'static str);
+
+impl Rendered {
+    /// Given `stringify!(key)` followed by `:`, which is kept if it is a JSON string and a colon.
+    /// A Rust string literal that is also a JSON string means the same in both, and anything
+    /// else is left for the key to be serialized as usual.
+    #[inline]
+    pub const fn new(key: &'static str) -> Self {
+        let bytes = key.as_bytes();
+        let len = bytes.len();
+        if len < 3 || bytes[0] != b'"' || bytes[len - 1] != b':' {
+            return Rendered("");
+        }
+        match string(bytes, 1) {
+            Some(end) if end == len - 1 => Rendered(key),
+            _ => Rendered(""),
+        }
+    }
+
+    /// The `"key":`, if the key was kept.
+    #[inline]
+    pub fn get(self) -> Option<&'static str> {
+        match self.0 {
+            "" => None,
+            key => Some(key),
+        }
+    }
+}
+
+impl<'de, T, U> KeyValuePairDe<'de> for (T, U)
+where
+    T: KeyValuePairDe<'de>,
+    U: KeyValuePairDe<'de>,
+{
+    fn key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, serde::de::value::Error>
+    where
+        K: serde::de::DeserializeSeed<'de>,
+    {
+        if !self.0.is_done() {
+            self.0.key_seed(seed)
+        } else {
+            self.1.key_seed(seed)
+        }
+    }
+
+    fn value_seed<V>(&mut self, seed: V) -> Result<V::Value, serde::de::value::Error>
+    where
+        V: serde::de::DeserializeSeed<'de>,
+    {
+        if !self.0.is_done() {
+            self.0.value_seed(seed)
+        } else {
+            self.1.value_seed(seed)
+        }
+    }
 }
 
 impl<'de, T, U> KeyValuePairDe<'de> for Option<KV<T, U>>
 where
-    T: serde::de::Deserializer<'de, Error = serde::de::value::Error>,
+    T: JsonKey + serde::de::Deserializer<'de, Error = serde::de::value::Error>,
     U: serde::de::Deserializer<'de, Error = serde::de::value::Error>,
 {
     fn key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, serde::de::value::Error>
@@ -17,7 +98,7 @@ where
         K: serde::de::DeserializeSeed<'de>,
     {
         match self.take() {
-            Some(KV::Pair(k, v)) => {
+            Some(KV::Pair(k, v) | KV::Rendered(_, k, v)) => {
                 *self = Some(KV::V(v));
                 seed.deserialize(k).map(Some)
             }
@@ -33,9 +114,11 @@ where
         W: serde::de::DeserializeSeed<'de>,
     {
         match self.take() {
-            Some(KV::Pair(..)) => Err(<serde::de::value::Error as serde::de::Error>::custom(
-                "should not call next_value when expecting a key",
-            )),
+            Some(KV::Pair(..) | KV::Rendered(..)) => {
+                Err(<serde::de::value::Error as serde::de::Error>::custom(
+                    "should not call next_value when expecting a key",
+                ))
+            }
             Some(KV::V(v)) => seed.deserialize(v),
             None => {
                 unimplemented!()
@@ -60,14 +143,14 @@ where
     }
 
     #[inline]
-    fn size(&self) -> usize {
-        self.0.size() + self.1.size()
+    fn size(&self) -> Option<usize> {
+        Some(self.0.size()? + self.1.size()?)
     }
 }
 
 impl<T, U> KeyValuePairSer for Option<KV<T, U>>
 where
-    T: serde::ser::Serialize,
+    T: JsonKey,
     U: serde::ser::Serialize,
 {
     #[inline]
@@ -75,7 +158,7 @@ where
     where
         S: serde::ser::SerializeMap,
     {
-        if let Some(KV::Pair(k, v)) = self {
+        if let Some(KV::Pair(k, v) | KV::Rendered(_, k, v)) = self {
             seq.serialize_key(k)?;
             seq.serialize_value(v)?;
         }
@@ -83,10 +166,134 @@ where
     }
 
     #[inline]
-    fn size(&self) -> usize {
-        1
+    fn size(&self) -> Option<usize> {
+        // entries left out of the object are `None`
+        Some(self.is_some() as usize)
+    }
+}
+
+impl<T, U> KeyValuePairFmt for (T, U)
+where
+    T: KeyValuePairFmt,
+    U: KeyValuePairFmt,
+{
+    #[inline]
+    fn fmt(&self, map: &mut Compound<'_, &mut fmt::Formatter<'_>, Compact>) -> Result<(), Error> {
+        self.0.fmt(map)?;
+        self.1.fmt(map)
+    }
+}
+
+impl<T, U> KeyValuePairFmt for Option<KV<T, U>>
+where
+    T: JsonKey,
+    U: fmt::Display,
+{
+    #[inline]
+    fn fmt(&self, map: &mut Compound<'_, &mut fmt::Formatter<'_>, Compact>) -> Result<(), Error> {
+        if let Some(KV::Rendered(key, _, v)) = self {
+            if let Some(key) = key.get() {
+                return map.rendered_entry(key, |f| v.fmt(f));
+            }
+        }
+        match self {
+            Some(KV::Pair(k, v) | KV::Rendered(_, k, v)) => {
+                serde::ser::SerializeMap::serialize_key(map, k)?;
+                map.value_with(|f| v.fmt(f))
+            }
+            _ => Ok(()),
+        }
     }
 }
+
+impl<T> KeyValuePairSer for Spread<T>
+where
+    T: serde::ser::Serialize,
+{
+    #[inline]
+    fn serialize<S>(&self, seq: &mut S) -> Result<(), S::Error>
+    where
+        S: serde::ser::SerializeMap,
+    {
+        self.0.serialize(FlatMapSerializer(seq))
+    }
+
+    #[inline]
+    fn size(&self) -> Option<usize> {
+        // the entries can't be counted without serializing the value twice
+        None
+    }
+}
+/// `for pat in iter => key: value` inside an object literal.
+#[doc(hidden)]
+#[derive(Clone, Copy)]
+pub struct Entries<I, F> {
+    iter: I,
+    f: F,
+}
+
+impl<I, F> Entries<I, F> {
+    #[inline]
+    pub fn new<K, V>(iter: I, f: F) -> Self
+    where
+        I: IntoIterator + Clone,
+        F: Fn(I::Item) -> (K, V),
+        K: JsonKey,
+    {
+        Entries { iter, f }
+    }
+}
+
+impl<I, F, K, V> KeyValuePairSer for Entries<I, F>
+where
+    I: IntoIterator + Clone,
+    F: Fn(I::Item) -> (K, V),
+    K: JsonKey,
+    V: serde::ser::Serialize,
+{
+    #[inline]
+    fn serialize<S>(&self, seq: &mut S) -> Result<(), S::Error>
+    where
+        S: serde::ser::SerializeMap,
+    {
+        for item in self.iter.clone() {
+            let (k, v) = (self.f)(item);
+            seq.serialize_entry(&k, &v)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn size(&self) -> Option<usize> {
+        exact_len(&self.iter.clone().into_iter())
+    }
+}
+
+impl<T: serde::ser::Serialize> KeyValuePairFmt for Spread<T> {
+    #[inline]
+    fn fmt(&self, map: &mut Compound<'_, &mut fmt::Formatter<'_>, Compact>) -> Result<(), Error> {
+        self.0.serialize(FlatMapSerializer(map))
+    }
+}
+
+impl<I, F, K, V> KeyValuePairFmt for Entries<I, F>
+where
+    I: IntoIterator + Clone,
+    F: Fn(I::Item) -> (K, V),
+    K: JsonKey,
+    V: fmt::Display,
+{
+    #[inline]
+    fn fmt(&self, map: &mut Compound<'_, &mut fmt::Formatter<'_>, Compact>) -> Result<(), Error> {
+        for item in self.iter.clone() {
+            let (k, v) = (self.f)(item);
+            serde::ser::SerializeMap::serialize_key(map, &k)?;
+            map.value_with(|f| v.fmt(f))?;
+        }
+        Ok(())
+    }
+}
+
 impl<'de> KeyValuePairDe<'de> for () {
     fn key_seed<K>(&mut self, _seed: K) -> Result<Option<K::Value>, serde::de::value::Error>
     where
@@ -114,8 +321,14 @@ impl KeyValuePairSer for () {
     }
 
     #[inline]
-    fn size(&self) -> usize {
-        0
+    fn size(&self) -> Option<usize> {
+        Some(0)
+    }
+}
+impl KeyValuePairFmt for () {
+    #[inline]
+    fn fmt(&self, _map: &mut Compound<'_, &mut fmt::Formatter<'_>, Compact>) -> Result<(), Error> {
+        Ok(())
     }
 }
 
@@ -132,13 +345,96 @@ pub trait KeyValuePairSer {
     fn serialize<S>(&self, seq: &mut S) -> Result<(), S::Error>
     where
         S: serde::ser::SerializeMap;
-    fn size(&self) -> usize;
+    fn size(&self) -> Option<usize>;
+}
+
+/// The entries of a [`Map`] for its compact `Display`, where each value is written by its own
+/// `Display` and the keys rendered by [`json!`](crate::json) are written as is.
+pub trait KeyValuePairFmt {
+    fn fmt(&self, map: &mut Compound<'_, &mut fmt::Formatter<'_>, Compact>) -> Result<(), Error>;
 }
 
+/// A JSON object, as built by [`json!`](crate::json).
+///
+/// The entries are nested into balanced pairs the same as the elements of an [`Array`](crate::Array).
+/// Each entry is an `Option<KV<K, V>>`, which is `None` for a conditional entry that is left
+/// out. A spread `..map` is an item of its own, which writes all the entries of the map.
+///
+/// ```
+/// # use typed_json::{json, Expr, Map, KV};
+/// #
+/// type User<'a> = Map<(
+///     Option<KV<Expr<&'static str>, Expr<u64>>>,
+///     Option<KV<Expr<&'static str>, Expr<&'a str>>>,
+/// )>;
+///
+/// let name = String::from("John Doe");
+/// let value: User = json!({ "id": 1, "name": name.as_str() });
+///
+/// assert_eq!(serde_json::to_string(&value).unwrap(), r#"{"id":1,"name":"John Doe"}"#);
+/// ```
 #[derive(Copy, Clone)]
-#[doc(hidden)]
 pub struct Map<T>(pub T);
 
+impl Map<()> {
+    /// An empty object, to build up with [`entry`](Map::entry) and [`optional`](Map::optional).
+    ///
+    /// This is for code where the entries depend on type parameters, so [`json!`](crate::json)
+    /// can't be used. Each call adds a new entry to the type, nested to the left, so
+    /// `Map::new().entry(a, 1).entry(b, 2)` holds `(((), a), b)` where `json!` holds the
+    /// balanced `(a, b)`. The two are different types, so a built map can't be used where a
+    /// `json!` object's type is written out, but the JSON is the same as the `json!` object with
+    /// the same entries.
+    ///
+    /// ```
+    /// # use typed_json::{json, Array, Map};
+    /// #
+    /// fn tagged<'a, T: serde::Serialize + 'a>(tag: &'a str, value: T) -> impl serde::Serialize + 'a {
+    ///     Map::new().entry("tag", tag).entry("value", value)
+    /// }
+    ///
+    /// let value = Map::new()
+    ///     .entry("a", 1)
+    ///     .entry("b", Array::new().push(true))
+    ///     .entry("c", tagged("id", 7));
+    ///
+    /// assert_eq!(
+    ///     serde_json::to_string(&value).unwrap(),
+    ///     serde_json::to_string(&json!({ "a": 1, "b": [true], "c": { "tag": "id", "value": 7 } }))
+    ///         .unwrap(),
+    /// );
+    /// ```
+    #[inline]
+    pub const fn new() -> Self {
+        Map(())
+    }
+}
+
+impl Default for Map<()> {
+    #[inline]
+    fn default() -> Self {
+        Map::new()
+    }
+}
+
+/// An entry added by [`Map::entry`] or [`Map::optional`].
+type Entry<K, V> = Option<KV<Expr<K>, Expr<V>>>;
+
+impl<T> Map<T> {
+    /// Add `key: value`, the same as an entry in [`json!`](crate::json).
+    #[inline]
+    pub fn entry<K: JsonKey, V>(self, key: K, value: V) -> Map<(T, Entry<K, V>)> {
+        Map((self.0, Some(KV::Pair(Expr(key), Expr(value)))))
+    }
+
+    /// Add `key: value` if `value` is `Some`, the same as `key?: value` in
+    /// [`json!`](crate::json).
+    #[inline]
+    pub fn optional<K: JsonKey, V>(self, key: K, value: Option<V>) -> Map<(T, Entry<K, V>)> {
+        Map((self.0, KV::optional(Expr(key), value)))
+    }
+}
+
 struct MapState<T>(T);
 
 impl<'de, T: KeyValuePairDe<'de>> crate::Deserializer<'de> for Map<T> {
@@ -183,7 +479,7 @@ impl<T: KeyValuePairSer> serde::ser::Serialize for Map<T> {
     where
         S: serde::Serializer,
     {
-        let mut seq = serializer.serialize_map(Some(self.0.size()))?;
+        let mut seq = serializer.serialize_map(self.0.size())?;
         self.0.serialize(&mut seq)?;
         serde::ser::SerializeMap::end(seq)
     }
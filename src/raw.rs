@@ -0,0 +1,494 @@
+use core::fmt;
+
+use serde::ser::{Error as _, Serialize, SerializeMap, SerializeSeq, SerializeStruct, Serializer};
+
+/// The name `serde_json` gives to [`RawValue`](https://docs.rs/serde_json/1/serde_json/value/struct.RawValue.html),
+/// which its serializer recognises and writes out verbatim. So does [`Serializer`](crate::Serializer).
+pub(crate) const TOKEN: &str = "$serde_json::private::RawValue";
+
+/// A fragment of JSON that is already encoded, spliced into the output as is.
+///
+/// This uses the same mechanism as `serde_json`'s `RawValue`, so `serde_json` writes the
+/// fragment verbatim without parsing it into a `Value` first, and so does this crate's
+/// [`Serializer`](crate::Serializer). Other serializers, like `serde_test`'s, will see a struct
+/// with a single string field instead, so serialize [`Raw::parsed`] for those. With the `std`
+/// feature, `serde_json`'s `raw_value` feature is enabled so that this works out of the box.
+///
+/// JSON that is only known at runtime, like a cached response body, should be validated with
+/// [`Raw::new`]. Inside [`json!`](crate::json), `raw!(...)` takes a compile-time string, which
+/// is checked at compile time. A string that isn't valid JSON has to be an [`InvalidJson`], so
+/// the compiler reports a type mismatch pointing at it.
+///
+/// ```compile_fail
+/// # use typed_json::json;
+/// #
+/// let value = json!({ "defaults": raw!(r#"{"theme":"#) });
+/// ```
+///
+/// ```
+/// # use typed_json::{json, Raw};
+/// #
+/// let cached = String::from(r#"{"id":1,"tags":["a","b"]}"#);
+/// let cached = Raw::new(&cached).unwrap();
+///
+/// let value = json!({
+///     "user": cached,
+///     "defaults": raw!(r#"{"theme":"dark"}"#),
+/// });
+///
+/// assert_eq!(
+///     serde_json::to_string(&value).unwrap(),
+///     r#"{"user":{"id":1,"tags":["a","b"]},"defaults":{"theme":"dark"}}"#,
+/// );
+/// ```
+///
+/// The fragment is written exactly as it is, whitespace and all, so pretty output like `{:#}`
+/// doesn't indent it to line up with the rest, the same as `serde_json::to_string_pretty` with a
+/// `RawValue`. Laying it out would mean parsing its numbers, which may not fit in an `f64`.
+/// Interpolate [`Raw::parsed`] to have the fragment laid out value by value instead.
+///
+/// ```
+/// # use typed_json::{json, Raw};
+/// #
+/// let raw = Raw::new_unchecked("[1,2]");
+///
+/// assert_eq!(format!("{:#}", json!({ "r": raw })), "{\n  \"r\": [1,2]\n}");
+/// assert_eq!(
+///     format!("{:#}", json!({ "r": raw.parsed() })),
+///     "{\n  \"r\": [\n    1,\n    2\n  ]\n}",
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Raw<'a>(&'a str);
+
+impl<'a> Raw<'a> {
+    /// Check that `json` is a single valid JSON value, with optional surrounding whitespace.
+    ///
+    /// ```
+    /// # use typed_json::Raw;
+    /// #
+    /// assert!(Raw::new(r#"{"a": [1, 2]}"#).is_ok());
+    /// assert!(Raw::new(r#"{"a": [1, 2"#).is_err());
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn new(json: &'a str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str::<serde::de::IgnoredAny>(json)?;
+        Ok(Raw(json))
+    }
+
+    /// Wrap `json` without checking that it is valid JSON.
+    ///
+    /// This is not unsafe, but if `json` is not a single valid JSON value then neither is the
+    /// output it is spliced into.
+    ///
+    /// ```
+    /// # use typed_json::Raw;
+    /// #
+    /// const EMPTY: Raw<'static> = Raw::new_unchecked("{}");
+    /// ```
+    #[inline]
+    pub const fn new_unchecked(json: &'a str) -> Self {
+        Raw(json)
+    }
+
+    /// The JSON fragment.
+    #[inline]
+    pub const fn get(&self) -> &'a str {
+        self.0
+    }
+
+    /// The fragment serialized value by value, for serializers that don't splice it in.
+    ///
+    /// ```
+    /// # use typed_json::Raw;
+    /// # use serde_test::{assert_ser_tokens, Token};
+    /// #
+    /// let raw = Raw::new_unchecked(r#"{"tags": ["a"]}"#);
+    ///
+    /// assert_ser_tokens(&raw.parsed(), &[
+    ///     Token::Map { len: Some(1) },
+    ///     Token::Str("tags"),
+    ///     Token::Seq { len: Some(1) },
+    ///     Token::Str("a"),
+    ///     Token::SeqEnd,
+    ///     Token::MapEnd,
+    /// ]);
+    /// ```
+    #[inline]
+    pub const fn parsed(&self) -> Parsed<'a> {
+        Parsed(self.0)
+    }
+}
+
+/// The result of checking the string in a `raw!` at compile time.
+#[doc(hidden)]
+pub struct Json<const VALID: bool>;
+
+/// The type the string in a `raw!` must have, given whether it is valid JSON.
+#[doc(hidden)]
+pub trait Valid {
+    type Str;
+}
+
+impl Valid for Json<true> {
+    type Str = &'static str;
+}
+
+impl Valid for Json<false> {
+    type Str = InvalidJson;
+}
+
+/// The type expected of the string in a `raw!` that isn't valid JSON.
+///
+/// Seeing this type in a compile error means the highlighted string is not a single JSON value.
+pub enum InvalidJson {}
+
+/// Whether `json` is a single valid JSON value, with optional surrounding whitespace, the same
+/// as [`Raw::new`] checks but at compile time.
+#[doc(hidden)]
+pub const fn is_valid(json: &str) -> bool {
+    let b = json.as_bytes();
+    match value(b, whitespace(b, 0)) {
+        Some(end) => whitespace(b, end) == b.len(),
+        None => false,
+    }
+}
+
+const fn whitespace(b: &[u8], mut i: usize) -> usize {
+    while i < b.len() && matches!(b[i], b' ' | b'\t' | b'\n' | b'\r') {
+        i += 1;
+    }
+    i
+}
+
+/// Where the value starting at `i` ends, if it is valid.
+const fn value(b: &[u8], i: usize) -> Option<usize> {
+    if i >= b.len() {
+        return None;
+    }
+    match b[i] {
+        b'{' => object(b, i + 1),
+        b'[' => array(b, i + 1),
+        b'"' => string(b, i + 1),
+        b't' => literal(b, i, b"true"),
+        b'f' => literal(b, i, b"false"),
+        b'n' => literal(b, i, b"null"),
+        b'-' | b'0'..=b'9' => number(b, i),
+        _ => None,
+    }
+}
+
+const fn object(b: &[u8], i: usize) -> Option<usize> {
+    let mut i = whitespace(b, i);
+    if i < b.len() && b[i] == b'}' {
+        return Some(i + 1);
+    }
+    loop {
+        if i >= b.len() || b[i] != b'"' {
+            return None;
+        }
+        i = match string(b, i + 1) {
+            Some(end) => whitespace(b, end),
+            None => return None,
+        };
+        if i >= b.len() || b[i] != b':' {
+            return None;
+        }
+        i = match value(b, whitespace(b, i + 1)) {
+            Some(end) => whitespace(b, end),
+            None => return None,
+        };
+        if i >= b.len() {
+            return None;
+        }
+        match b[i] {
+            b',' => i = whitespace(b, i + 1),
+            b'}' => return Some(i + 1),
+            _ => return None,
+        }
+    }
+}
+
+const fn array(b: &[u8], i: usize) -> Option<usize> {
+    let mut i = whitespace(b, i);
+    if i < b.len() && b[i] == b']' {
+        return Some(i + 1);
+    }
+    loop {
+        i = match value(b, i) {
+            Some(end) => whitespace(b, end),
+            None => return None,
+        };
+        if i >= b.len() {
+            return None;
+        }
+        match b[i] {
+            b',' => i = whitespace(b, i + 1),
+            b']' => return Some(i + 1),
+            _ => return None,
+        }
+    }
+}
+
+/// The rest of a string, after its opening `"`.
+pub(crate) const fn string(b: &[u8], mut i: usize) -> Option<usize> {
+    while i < b.len() {
+        match b[i] {
+            b'"' => return Some(i + 1),
+            b'\\' => {
+                i += 1;
+                if i >= b.len() {
+                    return None;
+                }
+                match b[i] {
+                    b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't' => i += 1,
+                    b'u' => {
+                        let mut n = 0;
+                        while n < 4 {
+                            i += 1;
+                            if i >= b.len() || !b[i].is_ascii_hexdigit() {
+                                return None;
+                            }
+                            n += 1;
+                        }
+                        i += 1;
+                    }
+                    _ => return None,
+                }
+            }
+            0..=0x1f => return None,
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+const fn literal(b: &[u8], i: usize, lit: &[u8]) -> Option<usize> {
+    let mut n = 0;
+    while n < lit.len() {
+        if i + n >= b.len() || b[i + n] != lit[n] {
+            return None;
+        }
+        n += 1;
+    }
+    Some(i + n)
+}
+
+const fn digits(b: &[u8], mut i: usize) -> usize {
+    while i < b.len() && b[i].is_ascii_digit() {
+        i += 1;
+    }
+    i
+}
+
+const fn number(b: &[u8], mut i: usize) -> Option<usize> {
+    if b[i] == b'-' {
+        i += 1;
+    }
+    // no leading zeros
+    if i < b.len() && b[i] == b'0' {
+        i += 1;
+    } else if i < b.len() && b[i].is_ascii_digit() {
+        i = digits(b, i);
+    } else {
+        return None;
+    }
+    if i < b.len() && b[i] == b'.' {
+        let start = i + 1;
+        i = digits(b, start);
+        if i == start {
+            return None;
+        }
+    }
+    if i < b.len() && (b[i] == b'e' || b[i] == b'E') {
+        i += 1;
+        if i < b.len() && (b[i] == b'+' || b[i] == b'-') {
+            i += 1;
+        }
+        let start = i;
+        i = digits(b, start);
+        if i == start {
+            return None;
+        }
+    }
+    Some(i)
+}
+
+impl Serialize for Raw<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_struct(TOKEN, 1)?;
+        s.serialize_field(TOKEN, self.0)?;
+        s.end()
+    }
+}
+
+/// A JSON fragment serialized value by value, from [`Raw::parsed`] or
+/// [`ConstJson::parsed`](crate::ConstJson::parsed).
+///
+/// Objects become maps, arrays sequences, `null` a unit, and numbers the first of `u64`, `i64`
+/// or `f64` that holds them, the same as serializing a `serde_json::Value`. This works with any
+/// serializer, but writing the fragment verbatim is quicker where that is supported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Parsed<'a>(&'a str);
+
+impl Serialize for Parsed<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let b = self.0.as_bytes();
+        let start = whitespace(b, 0);
+        match value(b, start) {
+            Some(end) if whitespace(b, end) == b.len() => {
+                Node(&self.0[start..end]).serialize(serializer)
+            }
+            _ => Err(S::Error::custom("the raw fragment is not valid JSON")),
+        }
+    }
+}
+
+/// A single JSON value that has already been checked, with no surrounding whitespace.
+struct Node<'a>(&'a str);
+
+impl Serialize for Node<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let json = self.0;
+        match json.as_bytes()[0] {
+            b'{' => {
+                let mut map = serializer.serialize_map(Some(Members::new(json).count() / 2))?;
+                let mut members = Members::new(json);
+                while let (Some(key), Some(value)) = (members.next(), members.next()) {
+                    map.serialize_entry(&key, &value)?;
+                }
+                map.end()
+            }
+            b'[' => {
+                let mut seq = serializer.serialize_seq(Some(Members::new(json).count()))?;
+                for element in Members::new(json) {
+                    seq.serialize_element(&element)?;
+                }
+                seq.end()
+            }
+            b'"' => {
+                let s = &json[1..json.len() - 1];
+                if s.contains('\\') {
+                    serializer.collect_str(&Unescape(s))
+                } else {
+                    serializer.serialize_str(s)
+                }
+            }
+            b't' => serializer.serialize_bool(true),
+            b'f' => serializer.serialize_bool(false),
+            b'n' => serializer.serialize_unit(),
+            _ => {
+                if !json.contains(|c| matches!(c, '.' | 'e' | 'E')) {
+                    if let Ok(n) = json.parse() {
+                        return serializer.serialize_u64(n);
+                    }
+                    if let Ok(n) = json.parse() {
+                        return serializer.serialize_i64(n);
+                    }
+                }
+                match json.parse() {
+                    Ok(n) => serializer.serialize_f64(n),
+                    Err(_) => Err(S::Error::custom("the raw fragment is not valid JSON")),
+                }
+            }
+        }
+    }
+}
+
+/// The values in an object or array, where the keys and values of an object take turns.
+struct Members<'a> {
+    json: &'a str,
+    i: usize,
+}
+
+impl<'a> Members<'a> {
+    fn new(json: &'a str) -> Self {
+        Members { json, i: 1 }
+    }
+}
+
+impl<'a> Iterator for Members<'a> {
+    type Item = Node<'a>;
+
+    fn next(&mut self) -> Option<Node<'a>> {
+        let b = self.json.as_bytes();
+        let start = whitespace(b, self.i);
+        let end = value(b, start)?;
+        self.i = whitespace(b, end) + 1;
+        Some(Node(&self.json[start..end]))
+    }
+}
+
+/// The contents of a string with escapes, written out unescaped.
+struct Unescape<'a>(&'a str);
+
+impl fmt::Display for Unescape<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rest = self.0;
+        while let Some(i) = rest.find('\\') {
+            f.write_str(&rest[..i])?;
+            let b = rest.as_bytes();
+            let (c, len) = match b[i + 1] {
+                b'b' => ('\u{8}', 2),
+                b'f' => ('\u{c}', 2),
+                b'n' => ('\n', 2),
+                b'r' => ('\r', 2),
+                b't' => ('\t', 2),
+                b'u' => {
+                    let high = hex(&rest[i + 2..i + 6]);
+                    let low = match rest.get(i + 6..i + 12) {
+                        Some(low) if (0xD800..0xDC00).contains(&high) && low.starts_with("\\u") => {
+                            Some(hex(&low[2..]))
+                        }
+                        _ => None,
+                    };
+                    match low {
+                        Some(low @ 0xDC00..=0xDFFF) => {
+                            let c = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+                            (char::from_u32(c).unwrap_or('\u{fffd}'), 12)
+                        }
+                        _ => (char::from_u32(high).unwrap_or('\u{fffd}'), 6),
+                    }
+                }
+                // `"`, `\` and `/`
+                c => (c as char, 2),
+            };
+            fmt::Write::write_char(f, c)?;
+            rest = &rest[i + len..];
+        }
+        f.write_str(rest)
+    }
+}
+
+/// Four hex digits that have already been checked.
+fn hex(digits: &str) -> u32 {
+    u32::from_str_radix(digits, 16).unwrap_or(0)
+}
+
+#[cfg(feature = "std")]
+impl<'de> crate::Deserializer<'de> for Raw<'de> {
+    fn deserialize_any2<V>(self, visitor: V) -> Result<V::Value, serde::de::value::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let mut de = serde_json::Deserializer::from_str(self.0);
+        serde::Deserializer::deserialize_any(&mut de, visitor)
+            .map_err(<serde::de::value::Error as serde::de::Error>::custom)
+    }
+}
+
+/// Writes the fragment as is, even with `{:#}`.
+impl fmt::Display for Raw<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
@@ -0,0 +1,1670 @@
+//! A JSON serializer that writes into any [`core::fmt::Write`], without `std` or an allocator.
+//!
+//! The output is the same as `serde_json`'s, compact or pretty, so that [`Display`](core::fmt::Display)
+//! for the `json!` types doesn't depend on `serde_json` writing valid UTF-8 to an `io::Write`.
+//! Floats are written by `ryu`, with a `+` on positive exponents like current `serde_json`.
+
+use core::fmt::{self, Display, Write};
+
+use serde::ser::{self, Impossible, Serialize};
+
+use crate::fmt::Formatted;
+use crate::raw::TOKEN;
+
+/// Serialize `value` as compact JSON into `writer`.
+///
+/// ```
+/// # use typed_json::json;
+/// #
+/// let mut out = String::new();
+/// typed_json::to_writer(&mut out, &json!({ "id": 1, "tags": ["a", "b"] })).unwrap();
+///
+/// assert_eq!(out, r#"{"id":1,"tags":["a","b"]}"#);
+/// ```
+#[inline]
+pub fn to_writer<W, T>(writer: W, value: &T) -> Result<(), Error>
+where
+    W: Write,
+    T: ?Sized + Serialize,
+{
+    value.serialize(&mut Serializer::new(writer))
+}
+
+/// Serialize `value` as pretty-printed JSON into `writer`, indented by two spaces.
+///
+/// ```
+/// # use typed_json::json;
+/// #
+/// let mut out = String::new();
+/// typed_json::to_writer_pretty(&mut out, &json!({ "id": 1, "tags": ["a"] })).unwrap();
+///
+/// assert_eq!(out, "{\n  \"id\": 1,\n  \"tags\": [\n    \"a\"\n  ]\n}");
+/// ```
+#[inline]
+pub fn to_writer_pretty<W, T>(writer: W, value: &T) -> Result<(), Error>
+where
+    W: Write,
+    T: ?Sized + Serialize,
+{
+    value.serialize(&mut Serializer::pretty(writer))
+}
+
+/// Serialize `value` as pretty-printed JSON into `writer`, keeping arrays and objects on one line
+/// when they fit within `width` columns.
+///
+/// ```
+/// # use typed_json::json;
+/// #
+/// let mut out = String::new();
+/// let value = json!({ "codes": [503, 2], "no": 503 });
+/// typed_json::to_writer_width(&mut out, &value, 20).unwrap();
+///
+/// assert_eq!(out, "{\n  \"codes\": [503,2],\n  \"no\": 503\n}");
+/// ```
+///
+/// The same layout is displayed by [`Pretty::width`].
+#[inline]
+pub fn to_writer_width<W, T>(writer: W, value: &T, width: usize) -> Result<(), Error>
+where
+    W: Write,
+    T: ?Sized + Serialize,
+{
+    Serializer::with_formatter(writer, Pretty::new().width(width)).write_value(value)
+}
+
+/// A [`serde::Serializer`] that writes JSON into a [`core::fmt::Write`].
+///
+/// Where the whitespace goes is up to the [`Formatter`], which is [`Compact`] or [`Pretty`].
+///
+/// ```
+/// # use serde::Serialize;
+/// # use typed_json::{json, Serializer};
+/// #
+/// let mut ser = Serializer::pretty(String::new());
+/// json!([1, null]).serialize(&mut ser).unwrap();
+///
+/// assert_eq!(ser.into_inner(), "[\n  1,\n  null\n]");
+/// ```
+pub struct Serializer<W, F = Compact> {
+    writer: W,
+    formatter: F,
+    escaping: Escaping,
+}
+
+impl<W: Write> Serializer<W> {
+    /// A serializer that writes compact JSON.
+    #[inline]
+    pub fn new(writer: W) -> Self {
+        Serializer::with_formatter(writer, Compact)
+    }
+}
+
+impl<W: Write> Serializer<W, Pretty<'static>> {
+    /// A serializer that writes pretty-printed JSON, indented by two spaces.
+    #[inline]
+    pub fn pretty(writer: W) -> Self {
+        Serializer::with_formatter(writer, Pretty::new())
+    }
+}
+
+impl<W: Write, F: Formatter> Serializer<W, F> {
+    /// A serializer that lays out the JSON with `formatter`.
+    #[inline]
+    pub fn with_formatter(writer: W, formatter: F) -> Self {
+        Serializer {
+            writer,
+            formatter,
+            escaping: Escaping::default(),
+        }
+    }
+
+    /// Also escape `<`, `>`, `&`, U+2028 and U+2029 in strings, so that the JSON can be inlined
+    /// into a `<script>` tag.
+    ///
+    /// ```
+    /// # use serde::Serialize;
+    /// # use typed_json::{json, Serializer};
+    /// #
+    /// let mut ser = Serializer::new(String::new()).html_safe();
+    /// json!({ "html": "</script>" }).serialize(&mut ser).unwrap();
+    ///
+    /// assert_eq!(ser.into_inner(), r#"{"html":"\u003c/script\u003e"}"#);
+    /// ```
+    #[inline]
+    pub fn html_safe(mut self) -> Self {
+        self.escaping.html = true;
+        self
+    }
+
+    /// Also escape every character outside of ASCII in strings, as `\uXXXX` or a surrogate pair
+    /// of them.
+    ///
+    /// ```
+    /// # use serde::Serialize;
+    /// # use typed_json::{json, Serializer};
+    /// #
+    /// let mut ser = Serializer::new(String::new()).ascii_only();
+    /// json!(["café", "🦀"]).serialize(&mut ser).unwrap();
+    ///
+    /// assert_eq!(ser.into_inner(), r#"["caf\u00e9","\ud83e\udd80"]"#);
+    /// ```
+    #[inline]
+    pub fn ascii_only(mut self) -> Self {
+        self.escaping.ascii = true;
+        self
+    }
+
+    #[inline]
+    pub(crate) fn with_escaping(mut self, escaping: Escaping) -> Self {
+        self.escaping = escaping;
+        self
+    }
+
+    /// Unwrap the writer.
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Write `value` as a JSON string, escaped as configured.
+    fn write_str(&mut self, value: &str) -> fmt::Result {
+        self.writer.write_char('"')?;
+        Escape::new(&mut self.writer, self.escaping).write_str(value)?;
+        self.writer.write_char('"')
+    }
+
+    /// Write `value`, letting the formatter measure it first.
+    pub(crate) fn write_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let next = Lookahead::new(value, false, self.escaping);
+        self.formatter.begin_value(&mut self.writer, next)?;
+        value.serialize(&mut *self)?;
+        Ok(self.formatter.end_value(&mut self.writer)?)
+    }
+}
+
+/// Where to put whitespace between the tokens of the JSON.
+///
+/// Each method writes the punctuation it is named after, along with any whitespace around it.
+pub trait Formatter {
+    /// Before the first element of an array.
+    fn begin_array<W: ?Sized + Write>(&mut self, writer: &mut W) -> fmt::Result {
+        writer.write_char('[')
+    }
+
+    /// After the last element of an array. `empty` is whether there were none.
+    fn end_array<W: ?Sized + Write>(&mut self, writer: &mut W, empty: bool) -> fmt::Result {
+        let _ = empty;
+        writer.write_char(']')
+    }
+
+    /// Before each element of an array.
+    fn begin_array_value<W: ?Sized + Write>(&mut self, writer: &mut W, first: bool) -> fmt::Result {
+        if first {
+            Ok(())
+        } else {
+            writer.write_char(',')
+        }
+    }
+
+    /// Before the first entry of an object.
+    fn begin_object<W: ?Sized + Write>(&mut self, writer: &mut W) -> fmt::Result {
+        writer.write_char('{')
+    }
+
+    /// After the last entry of an object. `empty` is whether there were none.
+    fn end_object<W: ?Sized + Write>(&mut self, writer: &mut W, empty: bool) -> fmt::Result {
+        let _ = empty;
+        writer.write_char('}')
+    }
+
+    /// Before the key of each entry of an object.
+    fn begin_object_key<W: ?Sized + Write>(&mut self, writer: &mut W, first: bool) -> fmt::Result {
+        self.begin_array_value(writer, first)
+    }
+
+    /// Between the key and the value of an entry.
+    fn begin_object_value<W: ?Sized + Write>(&mut self, writer: &mut W) -> fmt::Result {
+        writer.write_char(':')
+    }
+
+    /// Before the key of each entry is written, with the key, so that it can be measured.
+    fn object_key<W, T>(&mut self, writer: &mut W, key: Lookahead<'_, T>) -> fmt::Result
+    where
+        W: ?Sized + Write,
+        T: ?Sized + Serialize,
+    {
+        let _ = (writer, key);
+        Ok(())
+    }
+
+    /// Before an element, the value of an entry or a whole document is written, with the value,
+    /// so that it can be measured.
+    fn begin_value<W, T>(&mut self, writer: &mut W, value: Lookahead<'_, T>) -> fmt::Result
+    where
+        W: ?Sized + Write,
+        T: ?Sized + Serialize,
+    {
+        let _ = (writer, value);
+        Ok(())
+    }
+
+    /// After each value given to [`begin_value`](Formatter::begin_value) is written.
+    fn end_value<W: ?Sized + Write>(&mut self, writer: &mut W) -> fmt::Result {
+        let _ = writer;
+        Ok(())
+    }
+}
+
+/// A [`Formatter`] with no whitespace, the same as `serde_json::to_string`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Compact;
+
+impl Formatter for Compact {}
+
+/// A [`Formatter`] that puts each element and entry on its own line, by default the same as
+/// `serde_json::to_string_pretty`.
+///
+/// The whitespace can be changed to match a house style, and containers nested deeper than
+/// [`depth`](Pretty::depth) are written compactly.
+///
+/// ```
+/// # use typed_json::{json, Pretty};
+/// #
+/// let json = json!({ "name": "app", "ports": [80, 443] });
+/// let pretty = Pretty::new().indent("\t").newline("\r\n").separator(" = ").depth(1);
+///
+/// assert_eq!(
+///     pretty.display(&json).to_string(),
+///     "{\r\n\t\"name\" = \"app\",\r\n\t\"ports\" = [80,443]\r\n}",
+/// );
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Pretty<'a> {
+    indent: &'a str,
+    repeat: usize,
+    newline: &'a str,
+    separator: &'a str,
+    depth: usize,
+    width: Option<usize>,
+    level: usize,
+    /// The column the next value starts at, when there is a width.
+    column: usize,
+    /// The level of the container whose values are each kept on one line.
+    flat: Option<usize>,
+}
+
+impl<'a> Pretty<'a> {
+    /// Indent by two spaces, with `\n` between lines and `": "` between keys and values.
+    #[inline]
+    pub const fn new() -> Self {
+        Pretty {
+            indent: "  ",
+            repeat: 1,
+            newline: "\n",
+            separator: ": ",
+            depth: usize::MAX,
+            width: None,
+            level: 0,
+            column: 0,
+            flat: None,
+        }
+    }
+
+    /// Indent each level by `indent`.
+    #[inline]
+    pub const fn indent(self, indent: &'a str) -> Self {
+        Pretty {
+            indent,
+            repeat: 1,
+            ..self
+        }
+    }
+
+    /// Indent each level by `indent` repeated `repeat` times.
+    #[inline]
+    pub(crate) const fn indent_repeat(self, indent: &'a str, repeat: usize) -> Self {
+        Pretty {
+            indent,
+            repeat,
+            ..self
+        }
+    }
+
+    /// End each line with `newline`.
+    #[inline]
+    pub const fn newline(self, newline: &'a str) -> Self {
+        Pretty { newline, ..self }
+    }
+
+    /// Put `separator` between the key and the value of each entry.
+    #[inline]
+    pub const fn separator(self, separator: &'a str) -> Self {
+        Pretty { separator, ..self }
+    }
+
+    /// Only break the outermost `depth` levels of containers across lines.
+    ///
+    /// Anything nested deeper is written compactly, and a depth of 0 is the same as [`Compact`].
+    #[inline]
+    pub const fn depth(self, depth: usize) -> Self {
+        Pretty { depth, ..self }
+    }
+
+    /// Keep an array or object on one line when it fits within `width` columns, including the
+    /// indent and key before it and the comma after it.
+    ///
+    /// ```
+    /// # use typed_json::{json, Pretty};
+    /// #
+    /// let json = json!({ "id": 7, "codes": [200, 201, 204], "tags": ["a-long-tag", "another-long-tag"] });
+    ///
+    /// assert_eq!(
+    ///     Pretty::new().width(30).display(&json).to_string(),
+    ///     "{\n  \"id\": 7,\n  \"codes\": [200,201,204],\n  \"tags\": [\n    \"a-long-tag\",\n    \"another-long-tag\"\n  ]\n}",
+    /// );
+    /// ```
+    #[inline]
+    pub const fn width(self, width: usize) -> Self {
+        Pretty {
+            width: Some(width),
+            ..self
+        }
+    }
+
+    /// Display `value` with this layout.
+    #[inline]
+    pub fn display<T: ?Sized + Serialize>(self, value: &T) -> Formatted<'_, T, Self> {
+        Formatted::new(value, self)
+    }
+
+    /// Whether the innermost open container is broken across lines.
+    fn breaks(&self) -> bool {
+        self.level <= self.depth && self.flat.map_or(true, |flat| self.level <= flat)
+    }
+
+    fn line<W: ?Sized + Write>(&mut self, writer: &mut W) -> fmt::Result {
+        writer.write_str(self.newline)?;
+        for _ in 0..self.level * self.repeat {
+            writer.write_str(self.indent)?;
+        }
+        if self.width.is_some() {
+            self.column = self.level * self.repeat * self.indent.chars().count();
+        }
+        Ok(())
+    }
+}
+
+impl Default for Pretty<'_> {
+    #[inline]
+    fn default() -> Self {
+        Pretty::new()
+    }
+}
+
+impl Formatter for Pretty<'_> {
+    fn begin_array<W: ?Sized + Write>(&mut self, writer: &mut W) -> fmt::Result {
+        self.level += 1;
+        writer.write_char('[')
+    }
+
+    fn end_array<W: ?Sized + Write>(&mut self, writer: &mut W, empty: bool) -> fmt::Result {
+        if !empty && self.breaks() {
+            self.level -= 1;
+            self.line(writer)?;
+        } else {
+            self.level -= 1;
+        }
+        writer.write_char(']')
+    }
+
+    fn begin_array_value<W: ?Sized + Write>(&mut self, writer: &mut W, first: bool) -> fmt::Result {
+        if !first {
+            writer.write_char(',')?;
+        }
+        if self.breaks() {
+            self.line(writer)?;
+        }
+        Ok(())
+    }
+
+    fn begin_object<W: ?Sized + Write>(&mut self, writer: &mut W) -> fmt::Result {
+        self.level += 1;
+        writer.write_char('{')
+    }
+
+    fn end_object<W: ?Sized + Write>(&mut self, writer: &mut W, empty: bool) -> fmt::Result {
+        if !empty && self.breaks() {
+            self.level -= 1;
+            self.line(writer)?;
+        } else {
+            self.level -= 1;
+        }
+        writer.write_char('}')
+    }
+
+    fn begin_object_value<W: ?Sized + Write>(&mut self, writer: &mut W) -> fmt::Result {
+        if self.breaks() {
+            self.column += self.separator.chars().count();
+            writer.write_str(self.separator)
+        } else {
+            writer.write_char(':')
+        }
+    }
+
+    fn object_key<W, T>(&mut self, _writer: &mut W, key: Lookahead<'_, T>) -> fmt::Result
+    where
+        W: ?Sized + Write,
+        T: ?Sized + Serialize,
+    {
+        if self.width.is_some() && self.breaks() {
+            self.column += key.compact_len(usize::MAX).unwrap_or(0);
+        }
+        Ok(())
+    }
+
+    fn begin_value<W, T>(&mut self, _writer: &mut W, value: Lookahead<'_, T>) -> fmt::Result
+    where
+        W: ?Sized + Write,
+        T: ?Sized + Serialize,
+    {
+        if let Some(width) = self.width {
+            // only the values of a container that is broken across lines can be broken too
+            if self.flat.is_none() && self.level < self.depth {
+                let comma = if self.level == 0 { 0 } else { 1 };
+                if value
+                    .compact_len(width.saturating_sub(self.column + comma))
+                    .is_some()
+                {
+                    self.flat = Some(self.level);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn end_value<W: ?Sized + Write>(&mut self, _writer: &mut W) -> fmt::Result {
+        if self.flat == Some(self.level) {
+            self.flat = None;
+        }
+        Ok(())
+    }
+}
+
+/// A key or value that is about to be written, given to a [`Formatter`] so that it can be
+/// measured first.
+pub struct Lookahead<'a, T: ?Sized> {
+    value: &'a T,
+    key: bool,
+    escaping: Escaping,
+}
+
+impl<'a, T: ?Sized> Lookahead<'a, T> {
+    fn new(value: &'a T, key: bool, escaping: Escaping) -> Self {
+        Lookahead {
+            value,
+            key,
+            escaping,
+        }
+    }
+
+    /// The key or value itself.
+    pub fn get(&self) -> &'a T {
+        self.value
+    }
+}
+
+impl<T: ?Sized> Clone for Lookahead<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for Lookahead<'_, T> {}
+
+impl<T: ?Sized + Serialize> Lookahead<'_, T> {
+    /// How many characters the compact JSON takes, escaped the same way as it will be written, or
+    /// `None` if that is more than `limit`.
+    pub fn compact_len(&self, limit: usize) -> Option<usize> {
+        let mut measure = Measure::new(limit);
+        let mut ser = Serializer::new(&mut measure).with_escaping(self.escaping);
+        let result = if self.key {
+            self.value.serialize(KeySerializer(&mut ser))
+        } else {
+            self.value.serialize(&mut ser)
+        };
+        result.ok().map(|()| measure.len)
+    }
+}
+
+/// Counts the characters written through it, and fails once there are more than the limit.
+struct Measure {
+    len: usize,
+    limit: usize,
+}
+
+impl Measure {
+    fn new(limit: usize) -> Self {
+        Measure { len: 0, limit }
+    }
+}
+
+impl Write for Measure {
+    fn write_str(&mut self, value: &str) -> fmt::Result {
+        self.len += value.chars().count();
+        if self.len > self.limit {
+            Err(fmt::Error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// An error while serializing with a [`Serializer`].
+#[derive(Debug)]
+pub struct Error(pub(crate) ErrorKind);
+
+#[derive(Debug)]
+pub(crate) enum ErrorKind {
+    /// The writer failed.
+    Write,
+    KeyMustBeAString,
+    FloatKeyMustBeFinite,
+    /// NaN or an infinity in canonical JSON, which has no `null` fallback.
+    #[cfg(feature = "std")]
+    NumberMustBeFinite,
+    /// An error from a `Serialize` impl. The message needs an allocator to be kept.
+    #[cfg(feature = "std")]
+    Custom(String),
+    #[cfg(not(feature = "std"))]
+    Custom,
+}
+
+impl From<fmt::Error> for Error {
+    #[inline]
+    fn from(_: fmt::Error) -> Self {
+        Error(ErrorKind::Write)
+    }
+}
+
+impl From<Error> for fmt::Error {
+    #[inline]
+    fn from(_: Error) -> Self {
+        fmt::Error
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            ErrorKind::Write => f.write_str("failed to write JSON"),
+            ErrorKind::KeyMustBeAString => f.write_str("key must be a string"),
+            ErrorKind::FloatKeyMustBeFinite => {
+                f.write_str("float key must be finite (got NaN or +/-inf)")
+            }
+            #[cfg(feature = "std")]
+            ErrorKind::NumberMustBeFinite => {
+                f.write_str("number must be finite (got NaN or +/-inf)")
+            }
+            #[cfg(feature = "std")]
+            ErrorKind::Custom(msg) => f.write_str(msg),
+            #[cfg(not(feature = "std"))]
+            ErrorKind::Custom => f.write_str("failed to serialize"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+#[cfg(not(feature = "std"))]
+impl ser::StdError for Error {}
+
+impl ser::Error for Error {
+    #[cfg(feature = "std")]
+    fn custom<T: Display>(msg: T) -> Self {
+        Error(ErrorKind::Custom(msg.to_string()))
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn custom<T: Display>(_msg: T) -> Self {
+        Error(ErrorKind::Custom)
+    }
+}
+
+/// Which characters are escaped in strings, on top of the `"`, `\` and control characters that
+/// always are.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Escaping {
+    /// `<`, `>`, `&`, U+2028 and U+2029.
+    pub(crate) html: bool,
+    /// Everything outside of ASCII.
+    pub(crate) ascii: bool,
+}
+
+impl Escaping {
+    /// Whether only the characters JSON requires are escaped.
+    fn is_minimal(self) -> bool {
+        !self.html && !self.ascii
+    }
+}
+
+/// Write `value` as a JSON string, with quotes and escapes.
+#[cfg(feature = "std")]
+pub(crate) fn write_str<W: ?Sized + Write>(writer: &mut W, value: &str) -> fmt::Result {
+    writer.write_char('"')?;
+    Escape::new(writer, Escaping::default()).write_str(value)?;
+    writer.write_char('"')
+}
+
+/// Escapes everything written through it, as the inside of a JSON string.
+struct Escape<'a, W: ?Sized> {
+    writer: &'a mut W,
+    escaping: Escaping,
+    /// The input is JSON text rather than the inside of a string, so only the characters picked
+    /// by `escaping` are escaped. Any of those is inside a string in valid JSON.
+    json: bool,
+}
+
+impl<'a, W: ?Sized> Escape<'a, W> {
+    fn new(writer: &'a mut W, escaping: Escaping) -> Self {
+        Escape {
+            writer,
+            escaping,
+            json: false,
+        }
+    }
+
+    /// How `c` is escaped: as itself, as a short escape, or as `\uXXXX` for `""`.
+    fn escape(&self, c: char) -> Option<&'static str> {
+        let escape = match c {
+            _ if self.json => None,
+            '"' => Some("\\\""),
+            '\\' => Some("\\\\"),
+            '\n' => Some("\\n"),
+            '\r' => Some("\\r"),
+            '\t' => Some("\\t"),
+            '\u{8}' => Some("\\b"),
+            '\u{c}' => Some("\\f"),
+            '\0'..='\u{1f}' => Some(""),
+            _ => None,
+        };
+        escape.or(match c {
+            '<' | '>' | '&' | '\u{2028}' | '\u{2029}' if self.escaping.html => Some(""),
+            '\u{80}'..=char::MAX if self.escaping.ascii => Some(""),
+            _ => None,
+        })
+    }
+}
+
+impl<W: ?Sized + Write> Write for Escape<'_, W> {
+    fn write_str(&mut self, value: &str) -> fmt::Result {
+        let bytes = value.as_bytes();
+        let minimal = self.escaping.is_minimal();
+        let mut start = 0;
+        let mut i = 0;
+        while let Some(&byte) = bytes.get(i) {
+            if minimal && !ESCAPE[byte as usize] {
+                i += 1;
+                continue;
+            }
+            let c = if byte < 0x80 {
+                byte as char
+            } else if self.escaping.ascii || self.escaping.html && byte == 0xe2 {
+                // 0xe2 leads the UTF-8 of U+2028 and U+2029
+                value[i..].chars().next().unwrap_or_default()
+            } else {
+                i += 1;
+                continue;
+            };
+            let next = i + c.len_utf8();
+            if let Some(escape) = self.escape(c) {
+                self.writer.write_str(&value[start..i])?;
+                if escape.is_empty() {
+                    for unit in c.encode_utf16(&mut [0; 2]) {
+                        write!(self.writer, "\\u{:04x}", unit)?;
+                    }
+                } else {
+                    self.writer.write_str(escape)?;
+                }
+                start = next;
+            }
+            i = next;
+        }
+        self.writer.write_str(&value[start..])
+    }
+}
+
+/// The bytes that JSON requires to be escaped in a string: `"`, `\\` and control characters.
+static ESCAPE: [bool; 256] = {
+    let mut escape = [false; 256];
+    let mut byte = 0;
+    while byte < 0x20 {
+        escape[byte] = true;
+        byte += 1;
+    }
+    escape[b'"' as usize] = true;
+    escape[b'\\' as usize] = true;
+    escape
+};
+
+/// A finite float formatted by `ryu`, displayed the same way as `serde_json` with a `+` on
+/// positive exponents, so `1e+20` rather than `1e20`.
+struct Float<'a>(&'a str);
+
+impl Display for Float<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0.split_once('e') {
+            Some((mantissa, exp)) if !exp.starts_with('-') => {
+                write!(f, "{}e+{}", mantissa, exp)
+            }
+            _ => f.write_str(self.0),
+        }
+    }
+}
+
+/// Write a float the same way as `serde_json`, with `null` for NaN and infinities.
+fn write_f64<W: ?Sized + Write>(writer: &mut W, value: f64) -> fmt::Result {
+    if value.is_finite() {
+        write!(writer, "{}", Float(ryu::Buffer::new().format_finite(value)))
+    } else {
+        writer.write_str("null")
+    }
+}
+
+fn write_f32<W: ?Sized + Write>(writer: &mut W, value: f32) -> fmt::Result {
+    if value.is_finite() {
+        write!(writer, "{}", Float(ryu::Buffer::new().format_finite(value)))
+    } else {
+        writer.write_str("null")
+    }
+}
+
+impl<'a, W: Write, F: Formatter> ser::Serializer for &'a mut Serializer<W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Compound<'a, W, F>;
+    type SerializeTuple = Compound<'a, W, F>;
+    type SerializeTupleStruct = Compound<'a, W, F>;
+    type SerializeTupleVariant = Compound<'a, W, F>;
+    type SerializeMap = Compound<'a, W, F>;
+    type SerializeStruct = Compound<'a, W, F>;
+    type SerializeStructVariant = Compound<'a, W, F>;
+
+    #[inline]
+    fn serialize_bool(self, value: bool) -> Result<(), Error> {
+        Ok(self
+            .writer
+            .write_str(if value { "true" } else { "false" })?)
+    }
+
+    #[inline]
+    fn serialize_i8(self, value: i8) -> Result<(), Error> {
+        Ok(write!(self.writer, "{}", value)?)
+    }
+
+    #[inline]
+    fn serialize_i16(self, value: i16) -> Result<(), Error> {
+        Ok(write!(self.writer, "{}", value)?)
+    }
+
+    #[inline]
+    fn serialize_i32(self, value: i32) -> Result<(), Error> {
+        Ok(write!(self.writer, "{}", value)?)
+    }
+
+    #[inline]
+    fn serialize_i64(self, value: i64) -> Result<(), Error> {
+        Ok(write!(self.writer, "{}", value)?)
+    }
+
+    #[inline]
+    fn serialize_i128(self, value: i128) -> Result<(), Error> {
+        Ok(write!(self.writer, "{}", value)?)
+    }
+
+    #[inline]
+    fn serialize_u8(self, value: u8) -> Result<(), Error> {
+        Ok(write!(self.writer, "{}", value)?)
+    }
+
+    #[inline]
+    fn serialize_u16(self, value: u16) -> Result<(), Error> {
+        Ok(write!(self.writer, "{}", value)?)
+    }
+
+    #[inline]
+    fn serialize_u32(self, value: u32) -> Result<(), Error> {
+        Ok(write!(self.writer, "{}", value)?)
+    }
+
+    #[inline]
+    fn serialize_u64(self, value: u64) -> Result<(), Error> {
+        Ok(write!(self.writer, "{}", value)?)
+    }
+
+    #[inline]
+    fn serialize_u128(self, value: u128) -> Result<(), Error> {
+        Ok(write!(self.writer, "{}", value)?)
+    }
+
+    #[inline]
+    fn serialize_f32(self, value: f32) -> Result<(), Error> {
+        Ok(write_f32(&mut self.writer, value)?)
+    }
+
+    #[inline]
+    fn serialize_f64(self, value: f64) -> Result<(), Error> {
+        Ok(write_f64(&mut self.writer, value)?)
+    }
+
+    #[inline]
+    fn serialize_char(self, value: char) -> Result<(), Error> {
+        Ok(self.write_str(value.encode_utf8(&mut [0; 4]))?)
+    }
+
+    #[inline]
+    fn serialize_str(self, value: &str) -> Result<(), Error> {
+        Ok(self.write_str(value)?)
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<(), Error> {
+        let mut seq = ser::Serializer::serialize_seq(self, Some(value.len()))?;
+        for byte in value {
+            ser::SerializeSeq::serialize_element(&mut seq, byte)?;
+        }
+        ser::SerializeSeq::end(seq)
+    }
+
+    #[inline]
+    fn serialize_none(self) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    #[inline]
+    fn serialize_some<T>(self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    #[inline]
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(self.writer.write_str("null")?)
+    }
+
+    #[inline]
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    #[inline]
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.serialize_str(variant)
+    }
+
+    #[inline]
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.formatter.begin_object(&mut self.writer)?;
+        self.formatter.begin_object_key(&mut self.writer, true)?;
+        let key = Lookahead::new(variant, true, self.escaping);
+        self.formatter.object_key(&mut self.writer, key)?;
+        self.write_str(variant)?;
+        self.formatter.begin_object_value(&mut self.writer)?;
+        self.write_value(value)?;
+        Ok(self.formatter.end_object(&mut self.writer, false)?)
+    }
+
+    #[inline]
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        self.formatter.begin_array(&mut self.writer)?;
+        if len == Some(0) {
+            self.formatter.end_array(&mut self.writer, true)?;
+            return Ok(Compound::Empty);
+        }
+        Ok(Compound::Collection {
+            ser: self,
+            first: true,
+        })
+    }
+
+    #[inline]
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    #[inline]
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        self.formatter.begin_object(&mut self.writer)?;
+        self.formatter.begin_object_key(&mut self.writer, true)?;
+        let key = Lookahead::new(variant, true, self.escaping);
+        self.formatter.object_key(&mut self.writer, key)?;
+        self.write_str(variant)?;
+        self.formatter.begin_object_value(&mut self.writer)?;
+        let seq = self.serialize_seq(Some(len))?;
+        Ok(seq.in_variant())
+    }
+
+    #[inline]
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        self.formatter.begin_object(&mut self.writer)?;
+        if len == Some(0) {
+            self.formatter.end_object(&mut self.writer, true)?;
+            return Ok(Compound::Empty);
+        }
+        Ok(Compound::Collection {
+            ser: self,
+            first: true,
+        })
+    }
+
+    #[inline]
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        if name == TOKEN {
+            return Ok(Compound::Raw { ser: self });
+        }
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        self.formatter.begin_object(&mut self.writer)?;
+        self.formatter.begin_object_key(&mut self.writer, true)?;
+        let key = Lookahead::new(variant, true, self.escaping);
+        self.formatter.object_key(&mut self.writer, key)?;
+        self.write_str(variant)?;
+        self.formatter.begin_object_value(&mut self.writer)?;
+        let map = self.serialize_map(Some(len))?;
+        Ok(map.in_variant())
+    }
+
+    fn collect_str<T>(self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Display,
+    {
+        self.writer.write_char('"')?;
+        write!(Escape::new(&mut self.writer, self.escaping), "{}", value)?;
+        Ok(self.writer.write_char('"')?)
+    }
+}
+
+/// The state of an array or object while its elements or entries are written.
+#[doc(hidden)]
+pub enum Compound<'a, W, F> {
+    /// A collection that is known to be empty, which is already closed.
+    Empty,
+    /// A collection that is open, and whether nothing has been written to it yet.
+    Collection {
+        ser: &'a mut Serializer<W, F>,
+        first: bool,
+    },
+    /// The value of an enum variant, which closes the object around it too.
+    Variant {
+        ser: &'a mut Serializer<W, F>,
+        first: bool,
+    },
+    /// A [`Raw`](crate::Raw) fragment, which is written as is.
+    Raw { ser: &'a mut Serializer<W, F> },
+}
+
+impl<'a, W: Write, F: Formatter> Compound<'a, W, F> {
+    /// The same collection as the value of an enum variant, to close the object around it too.
+    fn in_variant(self) -> Self {
+        match self {
+            Compound::Collection { ser, first } => Compound::Variant { ser, first },
+            compound => compound,
+        }
+    }
+
+    /// Before an element or entry, and whether it is the first one.
+    fn next(&mut self) -> (&mut Serializer<W, F>, bool) {
+        match self {
+            Compound::Collection { ser, first } | Compound::Variant { ser, first } => {
+                (&mut **ser, core::mem::replace(first, false))
+            }
+            Compound::Empty | Compound::Raw { .. } => unreachable!("nothing is written here"),
+        }
+    }
+}
+
+/// The `Display` impls of [`json!`](crate::json) values write their elements and entries through
+/// these, so that a nested value is written by its own `Display`, and a key rendered at compile
+/// time is written as is.
+impl<W: Write> Compound<'_, W, Compact> {
+    /// An element of an array, written by `value`.
+    pub(crate) fn element_with<V>(&mut self, value: V) -> Result<(), Error>
+    where
+        V: FnOnce(&mut W) -> fmt::Result,
+    {
+        let (ser, first) = self.next();
+        ser.formatter.begin_array_value(&mut ser.writer, first)?;
+        Ok(value(&mut ser.writer)?)
+    }
+
+    /// An entry of an object, with its key already written out as `"key":`, and the value
+    /// written by `value`.
+    pub(crate) fn rendered_entry<V>(&mut self, key: &str, value: V) -> Result<(), Error>
+    where
+        V: FnOnce(&mut W) -> fmt::Result,
+    {
+        let (ser, first) = self.next();
+        ser.formatter.begin_object_key(&mut ser.writer, first)?;
+        ser.writer.write_str(key)?;
+        Ok(value(&mut ser.writer)?)
+    }
+
+    /// The value of an entry after [`serialize_key`](ser::SerializeMap::serialize_key), written
+    /// by `value`.
+    pub(crate) fn value_with<V>(&mut self, value: V) -> Result<(), Error>
+    where
+        V: FnOnce(&mut W) -> fmt::Result,
+    {
+        match self {
+            Compound::Collection { ser, .. } | Compound::Variant { ser, .. } => {
+                ser.formatter.begin_object_value(&mut ser.writer)?;
+                Ok(value(&mut ser.writer)?)
+            }
+            Compound::Empty | Compound::Raw { .. } => unreachable!("nothing is written here"),
+        }
+    }
+}
+
+impl<W: Write, F: Formatter> ser::SerializeSeq for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    #[inline]
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let (ser, first) = self.next();
+        ser.formatter.begin_array_value(&mut ser.writer, first)?;
+        ser.write_value(value)
+    }
+
+    #[inline]
+    fn end(self) -> Result<(), Error> {
+        match self {
+            Compound::Collection { ser, first } => {
+                Ok(ser.formatter.end_array(&mut ser.writer, first)?)
+            }
+            Compound::Variant { ser, first } => {
+                ser.formatter.end_array(&mut ser.writer, first)?;
+                Ok(ser.formatter.end_object(&mut ser.writer, false)?)
+            }
+            Compound::Empty | Compound::Raw { .. } => Ok(()),
+        }
+    }
+}
+
+impl<W: Write, F: Formatter> ser::SerializeTuple for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    #[inline]
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    #[inline]
+    fn end(self) -> Result<(), Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl<W: Write, F: Formatter> ser::SerializeTupleStruct for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    #[inline]
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    #[inline]
+    fn end(self) -> Result<(), Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl<W: Write, F: Formatter> ser::SerializeTupleVariant for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    #[inline]
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    #[inline]
+    fn end(self) -> Result<(), Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl<W: Write, F: Formatter> ser::SerializeMap for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    #[inline]
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let (ser, first) = self.next();
+        ser.formatter.begin_object_key(&mut ser.writer, first)?;
+        let next = Lookahead::new(key, true, ser.escaping);
+        ser.formatter.object_key(&mut ser.writer, next)?;
+        key.serialize(KeySerializer(ser))
+    }
+
+    #[inline]
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        match self {
+            Compound::Collection { ser, .. } | Compound::Variant { ser, .. } => {
+                ser.formatter.begin_object_value(&mut ser.writer)?;
+                ser.write_value(value)
+            }
+            Compound::Empty | Compound::Raw { .. } => unreachable!("nothing is written here"),
+        }
+    }
+
+    #[inline]
+    fn end(self) -> Result<(), Error> {
+        match self {
+            Compound::Collection { ser, first } => {
+                Ok(ser.formatter.end_object(&mut ser.writer, first)?)
+            }
+            Compound::Variant { ser, first } => {
+                ser.formatter.end_object(&mut ser.writer, first)?;
+                Ok(ser.formatter.end_object(&mut ser.writer, false)?)
+            }
+            Compound::Empty | Compound::Raw { .. } => Ok(()),
+        }
+    }
+}
+
+impl<W: Write, F: Formatter> ser::SerializeStruct for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    #[inline]
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if let Compound::Raw { ser } = self {
+            return value.serialize(RawSerializer(&mut ser.writer, ser.escaping));
+        }
+        ser::SerializeMap::serialize_entry(self, key, value)
+    }
+
+    #[inline]
+    fn end(self) -> Result<(), Error> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+impl<W: Write, F: Formatter> ser::SerializeStructVariant for Compound<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    #[inline]
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeMap::serialize_entry(self, key, value)
+    }
+
+    #[inline]
+    fn end(self) -> Result<(), Error> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+/// Writes the key of an object entry, which has to be a string. Numbers and bools are quoted,
+/// the same as `serde_json`.
+struct KeySerializer<'a, W, F>(&'a mut Serializer<W, F>);
+
+impl<W: Write, F: Formatter> KeySerializer<'_, W, F> {
+    fn quoted(self, value: impl Display) -> Result<(), Error> {
+        Ok(write!(self.0.writer, "\"{}\"", value)?)
+    }
+}
+
+impl<W: Write, F: Formatter> ser::Serializer for KeySerializer<'_, W, F> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Impossible<(), Error>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    #[inline]
+    fn serialize_str(self, value: &str) -> Result<(), Error> {
+        self.0.serialize_str(value)
+    }
+
+    #[inline]
+    fn serialize_char(self, value: char) -> Result<(), Error> {
+        self.0.serialize_char(value)
+    }
+
+    fn serialize_bool(self, value: bool) -> Result<(), Error> {
+        self.quoted(value)
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<(), Error> {
+        self.quoted(value)
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<(), Error> {
+        self.quoted(value)
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<(), Error> {
+        self.quoted(value)
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<(), Error> {
+        self.quoted(value)
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<(), Error> {
+        self.quoted(value)
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<(), Error> {
+        self.quoted(value)
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<(), Error> {
+        self.quoted(value)
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<(), Error> {
+        self.quoted(value)
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<(), Error> {
+        self.quoted(value)
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<(), Error> {
+        self.quoted(value)
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<(), Error> {
+        if !value.is_finite() {
+            return Err(Error(ErrorKind::FloatKeyMustBeFinite));
+        }
+        self.quoted(Float(ryu::Buffer::new().format_finite(value)))
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<(), Error> {
+        if !value.is_finite() {
+            return Err(Error(ErrorKind::FloatKeyMustBeFinite));
+        }
+        self.quoted(Float(ryu::Buffer::new().format_finite(value)))
+    }
+
+    fn serialize_bytes(self, _value: &[u8]) -> Result<(), Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    #[inline]
+    fn serialize_some<T>(self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    #[inline]
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.0.serialize_str(variant)
+    }
+
+    #[inline]
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Error(ErrorKind::KeyMustBeAString))
+    }
+
+    #[inline]
+    fn collect_str<T>(self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Display,
+    {
+        self.0.collect_str(value)
+    }
+}
+
+/// Writes the string inside a [`Raw`](crate::Raw) as is, apart from any escaping beyond what
+/// JSON requires.
+struct RawSerializer<'a, W>(&'a mut W, Escaping);
+
+impl<W: Write> ser::Serializer for RawSerializer<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Impossible<(), Error>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    #[inline]
+    fn serialize_str(self, value: &str) -> Result<(), Error> {
+        if self.1.is_minimal() {
+            return Ok(self.0.write_str(value)?);
+        }
+        let mut escape = Escape::new(self.0, self.1);
+        escape.json = true;
+        Ok(escape.write_str(value)?)
+    }
+
+    fn serialize_bool(self, _value: bool) -> Result<(), Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_i8(self, _value: i8) -> Result<(), Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_i16(self, _value: i16) -> Result<(), Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_i32(self, _value: i32) -> Result<(), Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_i64(self, _value: i64) -> Result<(), Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_u8(self, _value: u8) -> Result<(), Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_u16(self, _value: u16) -> Result<(), Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_u32(self, _value: u32) -> Result<(), Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_u64(self, _value: u64) -> Result<(), Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_f32(self, _value: f32) -> Result<(), Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_f64(self, _value: f64) -> Result<(), Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_char(self, _value: char) -> Result<(), Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_bytes(self, _value: &[u8]) -> Result<(), Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(raw_error())
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, _value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(raw_error())
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(raw_error())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(raw_error())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(raw_error())
+    }
+
+    fn collect_str<T>(self, _value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Display,
+    {
+        Err(raw_error())
+    }
+}
+
+fn raw_error() -> Error {
+    ser::Error::custom("expected a string of raw JSON")
+}
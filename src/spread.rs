@@ -0,0 +1,235 @@
+use serde::ser::{Error, Impossible, Serialize, SerializeMap, SerializeStruct, Serializer};
+
+/// `..value` inside a `json!` literal.
+///
+/// The contents of the value are emitted inline into the surrounding object or array.
+#[doc(hidden)]
+#[derive(Clone, Copy)]
+pub struct Spread<T>(pub T);
+
+fn not_a_map<E: Error>(kind: &str) -> E {
+    E::custom(format_args!(
+        "can only spread a map or struct into an object, found {}",
+        kind
+    ))
+}
+
+/// A [`Serializer`] that writes the entries of a map or struct into an existing map.
+pub(crate) struct FlatMapSerializer<'a, M>(pub(crate) &'a mut M);
+
+impl<'a, M: SerializeMap> Serializer for FlatMapSerializer<'a, M> {
+    type Ok = ();
+    type Error = M::Error;
+
+    type SerializeSeq = Impossible<(), M::Error>;
+    type SerializeTuple = Impossible<(), M::Error>;
+    type SerializeTupleStruct = Impossible<(), M::Error>;
+    type SerializeTupleVariant = Impossible<(), M::Error>;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), M::Error>;
+
+    fn serialize_bool(self, _v: bool) -> Result<(), M::Error> {
+        Err(not_a_map("a boolean"))
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<(), M::Error> {
+        Err(not_a_map("an integer"))
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<(), M::Error> {
+        Err(not_a_map("an integer"))
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<(), M::Error> {
+        Err(not_a_map("an integer"))
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<(), M::Error> {
+        Err(not_a_map("an integer"))
+    }
+
+    fn serialize_i128(self, _v: i128) -> Result<(), M::Error> {
+        Err(not_a_map("an integer"))
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<(), M::Error> {
+        Err(not_a_map("an integer"))
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<(), M::Error> {
+        Err(not_a_map("an integer"))
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<(), M::Error> {
+        Err(not_a_map("an integer"))
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<(), M::Error> {
+        Err(not_a_map("an integer"))
+    }
+
+    fn serialize_u128(self, _v: u128) -> Result<(), M::Error> {
+        Err(not_a_map("an integer"))
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<(), M::Error> {
+        Err(not_a_map("a float"))
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<(), M::Error> {
+        Err(not_a_map("a float"))
+    }
+
+    fn serialize_char(self, _v: char) -> Result<(), M::Error> {
+        Err(not_a_map("a char"))
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<(), M::Error> {
+        Err(not_a_map("a string"))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), M::Error> {
+        Err(not_a_map("bytes"))
+    }
+
+    fn collect_str<T: ?Sized + core::fmt::Display>(self, _value: &T) -> Result<(), M::Error> {
+        Err(not_a_map("a string"))
+    }
+
+    // nothing to spread
+    fn serialize_none(self) -> Result<(), M::Error> {
+        Ok(())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), M::Error> {
+        value.serialize(self)
+    }
+
+    // nothing to spread
+    fn serialize_unit(self) -> Result<(), M::Error> {
+        Ok(())
+    }
+
+    // nothing to spread
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), M::Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), M::Error> {
+        Err(not_a_map("an enum"))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), M::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), M::Error> {
+        Err(not_a_map("an enum"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, M::Error> {
+        Err(not_a_map("a sequence"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, M::Error> {
+        Err(not_a_map("a tuple"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, M::Error> {
+        Err(not_a_map("a tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, M::Error> {
+        Err(not_a_map("an enum"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, M::Error> {
+        Ok(self)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, M::Error> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, M::Error> {
+        Err(not_a_map("an enum"))
+    }
+}
+
+impl<'a, M: SerializeMap> SerializeMap for FlatMapSerializer<'a, M> {
+    type Ok = ();
+    type Error = M::Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), M::Error> {
+        self.0.serialize_key(key)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), M::Error> {
+        self.0.serialize_value(value)
+    }
+
+    fn serialize_entry<K: ?Sized + Serialize, V: ?Sized + Serialize>(
+        &mut self,
+        key: &K,
+        value: &V,
+    ) -> Result<(), M::Error> {
+        self.0.serialize_entry(key, value)
+    }
+
+    fn end(self) -> Result<(), M::Error> {
+        Ok(())
+    }
+}
+
+impl<'a, M: SerializeMap> SerializeStruct for FlatMapSerializer<'a, M> {
+    type Ok = ();
+    type Error = M::Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), M::Error> {
+        self.0.serialize_entry(key, value)
+    }
+
+    fn end(self) -> Result<(), M::Error> {
+        Ok(())
+    }
+}
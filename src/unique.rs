@@ -0,0 +1,65 @@
+//! Compile time checks that the literal keys of an object are unique.
+//!
+//! Each literal key is compared against the ones before it in a `const`, and the result picks the
+//! type that the key must have. A duplicate key must be a [`DuplicateKey`], so the compiler reports
+//! a type mismatch pointing at it.
+
+/// The result of checking a key against the keys before it.
+#[doc(hidden)]
+pub struct Key<const DUPLICATE: bool>;
+
+/// The type a key of type `T` must have, given whether it is a duplicate.
+#[doc(hidden)]
+pub trait Unique<T> {
+    type Key;
+}
+
+impl<T> Unique<T> for Key<false> {
+    type Key = T;
+}
+
+impl<T> Unique<T> for Key<true> {
+    type Key = DuplicateKey;
+}
+
+/// The type expected of a key that appears more than once in a `json!` object.
+///
+/// Seeing this type in a compile error means the highlighted key is already in the object.
+pub enum DuplicateKey {}
+
+impl serde::Serialize for DuplicateKey {
+    fn serialize<S>(&self, _serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match *self {}
+    }
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Whether `key` is one of the `seen` keys
+#[doc(hidden)]
+pub const fn contains(seen: &[&str], key: &str) -> bool {
+    let mut i = 0;
+    while i < seen.len() {
+        if str_eq(seen[i], key) {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
@@ -22,3 +22,19 @@ pub fn foo() -> Result<serde_json_core::heapless::String<256>, serde_json_core::
         },
     }))
 }
+
+pub fn bar() -> Result<serde_json_core::heapless::String<256>, core::fmt::Error> {
+    use core::fmt::Write;
+
+    let input = 1;
+    let value = typed_json::json!({
+        "foo": input,
+        "bar": [input, "two", null],
+    });
+
+    let mut out = serde_json_core::heapless::String::new();
+    write!(out, "{}", value)?;
+    write!(out, "{:#}", value)?;
+    typed_json::to_writer(&mut out, &value)?;
+    Ok(out)
+}
@@ -1,4 +1,4 @@
-#![recursion_limit = "512"]
+#![cfg_attr(not(feature = "proc-macro"), recursion_limit = "512")]
 fn main() {
     let data = typed_json::json! {{
       "description": "Service is a named abstraction of software service (for example, mysql) consisting of local port (for example 3306) that the proxy listens on, and the selector that determines which pods will answer requests sent through the proxy.",
@@ -25,3 +25,15 @@ fn serialize_string() {
 
     assert_eq!(str, "{\"foo\":1,\"bar\":[1],\"baz\":{\"code\":1,\"extra\":null,\"this\":{\"is\":{\"a\":[1,{\"really\":{\"deep\":[\"object\",1,null,true,false]}}]}}}}")
 }
+
+#[cfg(feature = "proc-macro")]
+#[test]
+fn diagnostics() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
+
+#[cfg(not(feature = "proc-macro"))]
+#[test]
+fn diagnostics() {
+    trybuild::TestCases::new().compile_fail("tests/ui/rules/*.rs");
+}
@@ -0,0 +1,9 @@
+use typed_json::json;
+
+fn main() {
+    let _ = json!({
+        "id": 1,
+        #[cfg(debug_assertions)]
+    });
+    let _ = json!([1, #[cfg(debug_assertions)]]);
+}
@@ -0,0 +1,12 @@
+use typed_json::json;
+
+fn main() {
+    let ok = true;
+
+    let _ = json!({ "result": if ok { 1 } });
+
+    let _ = json!(match ok {
+        true => 1
+        false => 2,
+    });
+}
@@ -0,0 +1,10 @@
+use typed_json::json_const;
+
+fn main() {
+    let code = 200;
+    let _ = json_const!({ "code": code });
+    let _ = json_const!({ 1: "one" });
+    let _ = json_const!([1, if true => 2]);
+    let _ = json_const!({ "message": f!("{}", code) });
+    let _ = json_const!({ "defaults": raw!("{\"theme\"}") });
+}
@@ -0,0 +1,9 @@
+use typed_json::json_type;
+
+type Message = json_type!({ "message": f!("{}", u64) });
+type Users = json_type!([for (&str, u64) => { "name": &str }]);
+type Bytes = json_type!(b"bytes");
+type Shorthand = json_type!({ name });
+type Length = json_type!([u8; n]);
+
+fn main() {}
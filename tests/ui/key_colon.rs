@@ -0,0 +1,10 @@
+use typed_json::json;
+
+fn main() {
+    let _ = json!({ 'key: loop { break 'key "code" }: 200 });
+
+    let id = 1;
+    let _ = json!({ id +: 200 });
+
+    let _ = json!({ "payload": "code": 200 });
+}
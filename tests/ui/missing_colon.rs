@@ -0,0 +1,9 @@
+use typed_json::json;
+
+fn main() {
+    let _ = json!({ "code" 200 });
+
+    let _ = json!({ "code" => 200 });
+
+    let _ = json!({ "code" });
+}
@@ -0,0 +1,10 @@
+use typed_json::json;
+
+fn main() {
+    let _ = json!({
+        "code": 200
+        "success": true,
+    });
+
+    let _ = json!([1, 2 3]);
+}
@@ -0,0 +1,8 @@
+use typed_json::json;
+
+fn main() {
+    let _ = json!({ "code": 200, "code": 404 });
+
+    let id = 1;
+    let _ = json!({ id, "id": 2 });
+}
@@ -0,0 +1,9 @@
+use typed_json::json;
+
+fn main() {
+    let _ = json!({ "defaults": raw!(r#"{"theme": }"#) });
+
+    let _ = json!([raw!("[1, 2")]);
+
+    let _ = json!([raw!(concat!("{", "]"))]);
+}
@@ -0,0 +1,14 @@
+use typed_json::json;
+
+fn main() {
+    let extra = std::collections::BTreeMap::<String, i32>::new();
+    let _ = json!({
+        #![sort_keys]
+        "id": 1,
+        ..extra,
+    });
+    let _ = json!({
+        #![sort_keys = "utf16"]
+        "id": 1,
+    });
+}
@@ -0,0 +1,9 @@
+use typed_json::json;
+
+fn main() {
+    let _ = json!({ "code": 200 } "extra");
+
+    let _ = json!({ "len": [1, 2].len() });
+
+    let _ = json!(null, null);
+}
@@ -0,0 +1,7 @@
+use typed_json::json;
+
+fn main() {
+    let _ = json!({
+        "features": ["serde", "json"}
+    });
+}